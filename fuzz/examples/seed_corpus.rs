@@ -0,0 +1,89 @@
+//! Writes the seed corpus of the fuzz targets, derived from `data/input.csv` and the CSV fixtures
+//! of the tests (raw string literals starting with `type`).
+//!
+//! Run with `cargo run --example seed_corpus` from the `fuzz` directory.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use tx_toy::InputStream;
+
+fn main() -> io::Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let mut fixtures = vec![(
+        "input".to_owned(),
+        fs::read_to_string(root.join("../data/input.csv"))?,
+    )];
+
+    for dir in ["../src", "../tests"] {
+        let mut paths = fs::read_dir(root.join(dir))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        paths.sort();
+
+        for path in paths {
+            if path.extension().is_some_and(|ext| ext == "rs") {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let prefix = dir.trim_start_matches("../");
+                let source = fs::read_to_string(&path)?;
+
+                for (n, csv) in csv_literals(&source).into_iter().enumerate() {
+                    fixtures.push((format!("{prefix}_{stem}_{n}"), csv));
+                }
+            }
+        }
+    }
+
+    let raw = root.join("corpus/raw_input");
+    let transactions = root.join("corpus/transactions");
+
+    fs::create_dir_all(&raw)?;
+    fs::create_dir_all(&transactions)?;
+
+    for (name, csv) in &fixtures {
+        // The first byte selects the decoding mode: lenient or strict.
+        for (mode, suffix) in [(0, "lenient"), (1, "strict")] {
+            let mut data = vec![mode];
+            data.extend_from_slice(csv.as_bytes());
+            fs::write(raw.join(format!("{name}_{suffix}")), data)?;
+        }
+
+        let rows = match InputStream::from_reader(io::Cursor::new(csv.clone())) {
+            Ok(input) => input
+                .filter_map(|record| record.ok())
+                .map(|record| record.data)
+                .collect::<Vec<_>>(),
+            Err(_) => continue,
+        };
+
+        if !rows.is_empty() {
+            fs::write(transactions.join(name), tx_toy_fuzz::encode(&rows))?;
+        }
+    }
+
+    println!("{} fixtures written", fixtures.len());
+
+    Ok(())
+}
+
+/// Extracts the raw string literals starting with the `type` header.
+fn csv_literals(source: &str) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("r\"type") {
+        rest = &rest[start + 2..];
+
+        let end = match rest.find('"') {
+            Some(end) => end,
+            None => break,
+        };
+
+        literals.push(rest[..end].to_owned());
+        rest = &rest[end..];
+    }
+
+    literals
+}
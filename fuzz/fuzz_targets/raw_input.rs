@@ -0,0 +1,33 @@
+//! Arbitrary bytes decoded by `InputStream` and processed by the engine.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+use tx_toy::{Engine, InputStream};
+
+fuzz_target!(|data: &[u8]| {
+    // Either decoding mode, chosen by the first byte.
+    let (strict, data) = match data.split_first() {
+        Some((mode, data)) => (mode & 1 == 1, data),
+        None => return,
+    };
+
+    // The stream owns its reader.
+    let input = match InputStream::from_reader(Cursor::new(data.to_vec())) {
+        Ok(input) if strict => input.strict(),
+        res => res,
+    };
+
+    let input = match input {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+
+    // Decoding errors are skipped, as the row after them may be valid.
+    let rows = input
+        .filter_map(|record| record.ok())
+        .map(|record| record.data);
+
+    tx_toy_fuzz::process(&mut Engine::new(), rows);
+});
@@ -0,0 +1,10 @@
+//! Structured transaction rows processed by an engine with every policy enabled.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tx_toy_fuzz::Transactions;
+
+fuzz_target!(|rows: Transactions| {
+    tx_toy_fuzz::process(&mut tx_toy_fuzz::configured_engine(), rows.0);
+});
@@ -0,0 +1,190 @@
+//! Shared code of the fuzz targets.
+//!
+//! The structured target decodes its input into transaction rows with a simple binary encoding
+//! rather than a derived `Arbitrary` implementation, so that the seed corpus can be encoded from
+//! the CSV fixtures (see `examples/seed_corpus.rs`).
+
+use arbitrary::{Arbitrary, Unstructured};
+use rust_decimal::Decimal;
+use tx_toy::{ClientId, Engine, EngineConfig, RawTransactionData, TransactionId, TransactionType};
+
+/// Transaction types, by their encoded index.
+const TYPES: [TransactionType; 10] = [
+    TransactionType::Deposit,
+    TransactionType::Withdrawal,
+    TransactionType::Dispute,
+    TransactionType::Resolve,
+    TransactionType::Chargeback,
+    TransactionType::CreditLimit,
+    TransactionType::Accrue,
+    TransactionType::Representment,
+    TransactionType::Begin,
+    TransactionType::Commit,
+];
+
+const HAS_AMOUNT: u8 = 0b001;
+const NEGATIVE: u8 = 0b010;
+const HAS_TIMESTAMP: u8 = 0b100;
+
+/// A sequence of transaction rows, each encoded as:
+///
+/// - type index (1 byte, modulo the number of types);
+/// - client and transaction IDs (`u16` each, little endian);
+/// - flags (1 byte): amount present, amount negative, timestamp present;
+/// - amount, if present: 96-bit mantissa (12 bytes, little endian) and scale (1 byte, modulo 29);
+/// - timestamp, if present (`u64`, little endian);
+///
+/// Missing trailing bytes are read as zeros.
+#[derive(Debug)]
+pub struct Transactions(pub Vec<RawTransactionData>);
+
+impl<'a> Arbitrary<'a> for Transactions {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut rows = Vec::new();
+
+        while !u.is_empty() {
+            rows.push(decode(u));
+        }
+
+        Ok(Self(rows))
+    }
+}
+
+fn take<const N: usize>(u: &mut Unstructured) -> [u8; N] {
+    let mut buf = [0; N];
+    let len = u.len().min(N);
+
+    if let Ok(bytes) = u.bytes(len) {
+        buf[..len].copy_from_slice(bytes);
+    }
+
+    buf
+}
+
+fn decode(u: &mut Unstructured) -> RawTransactionData {
+    let [ty] = take::<1>(u);
+    let client = u16::from_le_bytes(take(u));
+    let id = u16::from_le_bytes(take(u));
+    let [flags] = take::<1>(u);
+
+    let amount = (flags & HAS_AMOUNT != 0).then(|| {
+        let mut mantissa = [0; 16];
+        mantissa[..12].copy_from_slice(&take::<12>(u));
+
+        let [scale] = take::<1>(u);
+        let mut amount =
+            Decimal::from_i128_with_scale(i128::from_le_bytes(mantissa), u32::from(scale) % 29);
+
+        amount.set_sign_negative(flags & NEGATIVE != 0);
+        amount
+    });
+
+    let timestamp = (flags & HAS_TIMESTAMP != 0).then(|| u64::from_le_bytes(take(u)));
+
+    RawTransactionData {
+        id: TransactionId::from(id),
+        ty: TYPES[usize::from(ty) % TYPES.len()],
+        account_id: ClientId::from(client),
+        amount,
+        timestamp,
+    }
+}
+
+/// Encodes the rows as decoded by [`Transactions`]. IDs are truncated to 16 bits.
+pub fn encode(rows: &[RawTransactionData]) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    for row in rows {
+        let ty = TYPES.iter().position(|ty| *ty == row.ty).unwrap_or(0);
+
+        buf.push(ty as u8);
+        buf.extend_from_slice(&(row.account_id as u16).to_le_bytes());
+        buf.extend_from_slice(&(row.id as u16).to_le_bytes());
+
+        let mut flags = 0;
+
+        if let Some(amount) = row.amount {
+            flags |= HAS_AMOUNT;
+
+            if amount.is_sign_negative() {
+                flags |= NEGATIVE;
+            }
+        }
+
+        if row.timestamp.is_some() {
+            flags |= HAS_TIMESTAMP;
+        }
+
+        buf.push(flags);
+
+        if let Some(amount) = row.amount {
+            buf.extend_from_slice(&amount.mantissa().unsigned_abs().to_le_bytes()[..12]);
+            buf.push(amount.scale() as u8);
+        }
+
+        if let Some(timestamp) = row.timestamp {
+            buf.extend_from_slice(&timestamp.to_le_bytes());
+        }
+    }
+
+    buf
+}
+
+/// Creates an engine with every policy of the example configuration enabled.
+pub fn configured_engine() -> Engine {
+    let config = include_str!("../../data/config.toml");
+    Engine::with_config(toml::from_str::<EngineConfig>(config).expect("Valid example config"))
+}
+
+/// Processes the rows to the end of the input, checking the ledger after every one of them.
+pub fn process<I>(engine: &mut Engine, rows: I)
+where
+    I: IntoIterator<Item = RawTransactionData>,
+{
+    for row in rows {
+        let _ = engine.process_transaction(&row);
+
+        if let Err(err) = engine.audit() {
+            panic!("Ledger audit failed after {row:?}: {err}");
+        }
+    }
+
+    let _ = engine.finish();
+
+    if let Err(err) = engine.audit() {
+        panic!("Ledger audit failed at the end of the input: {err}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, Transactions};
+    use arbitrary::{Arbitrary, Unstructured};
+    use rust_decimal::Decimal;
+    use tx_toy::{RawTransactionData, TransactionType};
+
+    #[test]
+    fn encoding_round_trip() {
+        let rows = vec![
+            RawTransactionData {
+                id: 1,
+                ty: TransactionType::Deposit,
+                account_id: 2,
+                amount: Some(Decimal::new(-12345, 3)),
+                timestamp: Some(1000),
+            },
+            RawTransactionData {
+                id: 1,
+                ty: TransactionType::Commit,
+                account_id: 65535,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+
+        let data = encode(&rows);
+        let decoded = Transactions::arbitrary_take_rest(Unstructured::new(&data)).unwrap();
+
+        assert_eq!(decoded.0, rows);
+    }
+}
@@ -1,4 +1,7 @@
+use crate::batch::Journal;
+use crate::id::ClientId;
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error as ThisError;
 
@@ -15,6 +18,26 @@ pub enum BalanceOperationError {
         requested: Decimal,
         available: Decimal,
     },
+
+    #[error("Credit limit exceeded: Limit={limit} Available={available}")]
+    CreditLimitExceeded { limit: Decimal, available: Decimal },
+
+    #[error("Amount overflow: Amount={amount}")]
+    AmountOverflow { amount: Decimal },
+}
+
+impl BalanceOperationError {
+    /// Short machine-readable name of the error kind, for aggregation purposes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BalanceOperationError::InsufficientAvailableFunds { .. } => {
+                "insufficient_available_funds"
+            }
+            BalanceOperationError::InsufficientHeldFunds { .. } => "insufficient_held_funds",
+            BalanceOperationError::CreditLimitExceeded { .. } => "credit_limit_exceeded",
+            BalanceOperationError::AmountOverflow { .. } => "amount_overflow",
+        }
+    }
 }
 
 /// Represents an atomic account balance operation.
@@ -24,20 +47,35 @@ pub enum BalanceOperation {
     Hold(Decimal),
     Release(Decimal),
     WithdrawHeld(Decimal),
+    SetCreditLimit(Decimal),
+
+    /// Withdraws the funds regardless of the available ones and the credit limit, e.g. for the
+    /// house account refunding fees.
+    Debit(Decimal),
+}
+
+/// Credit line of a client account, as specified in the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreditLimit {
+    pub client: ClientId,
+    pub limit: Decimal,
 }
 
 /// Client account balance representation.
 ///
 /// Provides interface for updating balance with common transaction operations, returning errors
 /// in case of invalid balance during an operation.
+///
+/// The available funds can only go negative within the credit limit, which is zero by default.
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct AccountBalance {
     available: Decimal,
     held: Decimal,
     total: Decimal,
+    credit_limit: Decimal,
 }
 
-#[allow(dead_code)]
 impl AccountBalance {
     pub fn new() -> Self {
         Default::default()
@@ -51,35 +89,57 @@ impl AccountBalance {
     }
 
     /// Executes a balance operation atomically.
+    ///
+    /// Operations overflowing the balance amounts, or losing their precision, are rejected with
+    /// [`BalanceOperationError::AmountOverflow`], leaving the balance unchanged.
     pub fn update(&mut self, op: BalanceOperation) -> Result<(), BalanceOperationError> {
+        let (mut total, mut held) = (self.total, self.held);
+
         match op {
             BalanceOperation::Deposit(amount) => {
-                self.total += amount;
+                total = checked(exact_add(total, amount), amount)?;
             }
 
             BalanceOperation::WithdrawAvailable(amount) => {
                 self.validate_available_amount(amount)?;
-                self.total -= amount;
+                total = checked(exact_sub(total, amount), amount)?;
+            }
+
+            BalanceOperation::Debit(amount) => {
+                total = checked(exact_sub(total, amount), amount)?;
             }
 
             BalanceOperation::WithdrawHeld(amount) => {
                 self.validate_held_amount(amount)?;
-                self.held -= amount;
-                self.total -= amount;
+                held = checked(exact_sub(held, amount), amount)?;
+                total = checked(exact_sub(total, amount), amount)?;
             }
 
             BalanceOperation::Hold(amount) => {
                 self.validate_available_amount(amount)?;
-                self.held += amount;
+                held = checked(exact_add(held, amount), amount)?;
             }
 
             BalanceOperation::Release(amount) => {
                 self.validate_held_amount(amount)?;
-                self.held -= amount;
+                held = checked(exact_sub(held, amount), amount)?;
+            }
+
+            BalanceOperation::SetCreditLimit(limit) => {
+                if self.available.saturating_add(limit) < Decimal::ZERO {
+                    return Err(BalanceOperationError::CreditLimitExceeded {
+                        limit,
+                        available: self.available,
+                    });
+                }
+
+                self.credit_limit = limit;
             }
         }
 
-        self.available = self.total - self.held;
+        self.available = checked(exact_sub(total, held), held)?;
+        self.total = total;
+        self.held = held;
 
         Ok(())
     }
@@ -99,12 +159,19 @@ impl AccountBalance {
         self.total
     }
 
+    #[inline]
+    pub fn credit_limit(&self) -> Decimal {
+        self.credit_limit
+    }
+
     #[inline]
     fn validate_available_amount(&self, amount: Decimal) -> Result<(), BalanceOperationError> {
-        if self.available < amount {
+        let available = self.available.saturating_add(self.credit_limit);
+
+        if available < amount {
             Err(BalanceOperationError::InsufficientAvailableFunds {
                 requested: amount,
-                available: self.available,
+                available,
             })
         } else {
             Ok(())
@@ -124,33 +191,68 @@ impl AccountBalance {
     }
 }
 
+#[inline]
+fn checked(result: Option<Decimal>, amount: Decimal) -> Result<Decimal, BalanceOperationError> {
+    result.ok_or(BalanceOperationError::AmountOverflow { amount })
+}
+
+/// Adds the amounts, unless the sum is out of the decimal range or would have to be rounded to
+/// fit into it. The sum of non-zero amounts keeps the larger scale of them as long as it's exact.
+///
+/// Adding a zero returns the other amount as is, since the decimal sum of zeros may come out
+/// negative.
+#[inline]
+pub(crate) fn exact_add(a: Decimal, b: Decimal) -> Option<Decimal> {
+    if b.is_zero() {
+        return Some(a);
+    }
+
+    if a.is_zero() {
+        return Some(b);
+    }
+
+    a.checked_add(b)
+        .filter(|sum| sum.scale() >= a.scale().max(b.scale()))
+}
+
+/// Subtracts the amounts, unless the difference is out of the decimal range or would have to be
+/// rounded to fit into it.
+#[inline]
+pub(crate) fn exact_sub(a: Decimal, b: Decimal) -> Option<Decimal> {
+    exact_add(a, -b)
+}
+
 /// Client account data.
 ///
 /// Provides an interface to account balance. Not directly serializable (at least not into CSV),
 /// due to nested balance structure.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct AccountData {
-    id: u16,
+    id: ClientId,
     balance: AccountBalance,
     locked: bool,
+
+    /// Number of the account's transactions charged back and not reinstated.
+    chargebacks: usize,
 }
 
 impl AccountData {
-    pub fn new(id: u16) -> Self {
+    pub fn new(id: ClientId) -> Self {
         Self {
             id,
             balance: Default::default(),
             locked: false,
+            chargebacks: 0,
         }
     }
 
     #[inline]
-    pub fn id(&self) -> u16 {
+    pub fn id(&self) -> ClientId {
         self.id
     }
 
     #[inline]
-    pub fn set_locked(&mut self, locked: bool) {
+    pub(crate) fn set_locked(&mut self, locked: bool) {
         self.locked = locked;
     }
 
@@ -159,64 +261,138 @@ impl AccountData {
         self.locked
     }
 
+    #[inline]
+    pub(crate) fn chargebacks(&self) -> usize {
+        self.chargebacks
+    }
+
+    #[inline]
+    pub(crate) fn set_chargebacks(&mut self, chargebacks: usize) {
+        self.chargebacks = chargebacks;
+    }
+
     #[inline]
     pub fn balance(&self) -> &AccountBalance {
         &self.balance
     }
 
     #[inline]
-    pub fn balance_mut(&mut self) -> &mut AccountBalance {
+    pub(crate) fn balance_mut(&mut self) -> &mut AccountBalance {
         &mut self.balance
     }
 }
 
+/// An intermediate representation of account data for serialization purposes. As it turned out,
+/// `csv` crate doesn't supported nested struct serialization (even with serde's `flatten` switch).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+impl From<&AccountData> for AccountInfo {
+    fn from(account: &AccountData) -> Self {
+        Self {
+            client: account.id,
+            available: account.balance.available,
+            held: account.balance.held,
+            total: account.balance.total,
+            locked: account.locked,
+        }
+    }
+}
+
 /// Account database.
 ///
 /// A thin wrapper around a hashmap data storage.
-pub struct AccountStore {
-    data: HashMap<u16, AccountData>,
+#[derive(Clone)]
+pub(crate) struct AccountStore {
+    data: HashMap<ClientId, AccountData>,
+
+    /// Credit limits assigned to the accounts upon creation.
+    credit_limits: HashMap<ClientId, Decimal>,
+
+    /// Changes of the open batch, if there's one.
+    journal: Journal<ClientId, AccountData>,
 }
 
 impl AccountStore {
-    pub fn new() -> Self {
+    pub fn with_credit_limits(credit_limits: Vec<CreditLimit>) -> Self {
         Self {
             data: HashMap::new(),
+            credit_limits: credit_limits
+                .into_iter()
+                .map(|entry| (entry.client, entry.limit))
+                .collect(),
+            journal: Journal::new(),
         }
     }
 
     #[inline]
     #[allow(dead_code)]
-    pub fn exists(&self, id: u16) -> bool {
+    pub fn exists(&self, id: ClientId) -> bool {
         self.data.contains_key(&id)
     }
 
     #[inline]
-    pub fn get(&self, id: u16) -> Option<&AccountData> {
+    pub fn get(&self, id: ClientId) -> Option<&AccountData> {
         self.data.get(&id)
     }
 
     #[inline]
-    pub fn get_mut(&mut self, id: u16) -> &mut AccountData {
-        self.data.entry(id).or_insert_with(|| AccountData::new(id))
+    pub fn get_mut(&mut self, id: ClientId) -> &mut AccountData {
+        self.journal.record(id, self.data.get(&id));
+
+        let credit_limits = &self.credit_limits;
+
+        self.data.entry(id).or_insert_with(|| {
+            let mut account = AccountData::new(id);
+
+            if let Some(limit) = credit_limits.get(&id) {
+                account.balance.credit_limit = *limit;
+            }
+
+            account
+        })
     }
 
     #[inline]
     #[allow(dead_code)]
-    pub fn balance(&self, id: u16) -> Option<&AccountBalance> {
+    pub fn balance(&self, id: ClientId) -> Option<&AccountBalance> {
         self.get(id).map(|account| &account.balance)
     }
 
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = &AccountData> {
-        self.data.iter().map(|(_, data)| data)
+        self.data.values()
+    }
+
+    /// Starts journaling the changes, so that they can be rolled back.
+    pub fn begin(&mut self) {
+        self.journal.start();
+    }
+
+    /// Keeps the changes made since [`Self::begin`].
+    pub fn commit(&mut self) {
+        self.journal.commit();
+    }
+
+    /// Reverts the changes made since [`Self::begin`].
+    pub fn rollback(&mut self) {
+        self.journal.rollback(&mut self.data);
     }
 }
 
-#[cfg(test)]
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
 mod test {
     use super::AccountBalance;
     use super::BalanceOperation;
     use crate::account::BalanceOperationError;
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
 
     #[test]
@@ -230,7 +406,8 @@ mod test {
             AccountBalance {
                 available: dec!(1.5),
                 held: dec!(0.0),
-                total: dec!(1.5)
+                total: dec!(1.5),
+                ..Default::default()
             }
         );
 
@@ -241,7 +418,8 @@ mod test {
             AccountBalance {
                 available: dec!(0.75),
                 held: dec!(0.75),
-                total: dec!(1.5)
+                total: dec!(1.5),
+                ..Default::default()
             }
         );
 
@@ -252,7 +430,8 @@ mod test {
             AccountBalance {
                 available: dec!(0.75),
                 held: dec!(0.70),
-                total: dec!(1.45)
+                total: dec!(1.45),
+                ..Default::default()
             }
         );
 
@@ -263,7 +442,8 @@ mod test {
             AccountBalance {
                 available: dec!(0.95),
                 held: dec!(0.50),
-                total: dec!(1.45)
+                total: dec!(1.45),
+                ..Default::default()
             }
         );
 
@@ -274,7 +454,8 @@ mod test {
             AccountBalance {
                 available: dec!(0.0),
                 held: dec!(0.50),
-                total: dec!(0.5)
+                total: dec!(0.5),
+                ..Default::default()
             }
         );
 
@@ -297,4 +478,68 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn balance_op_overflow() -> anyhow::Result<()> {
+        let mut balance = AccountBalance::with_amount(dec!(10.0), dec!(5.0))?;
+
+        assert!(matches!(
+            balance.update(BalanceOperation::Deposit(Decimal::MAX)),
+            Err(BalanceOperationError::AmountOverflow { .. })
+        ));
+
+        // The sum would have to be rounded to fit into the decimal range.
+        assert!(matches!(
+            balance.update(BalanceOperation::Deposit(dec!(
+                0.0000000000000000000000000001
+            ))),
+            Err(BalanceOperationError::AmountOverflow { .. })
+        ));
+
+        assert_eq!(balance, AccountBalance::with_amount(dec!(10.0), dec!(5.0))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn zero_balance_sign() -> anyhow::Result<()> {
+        let mut balance = AccountBalance::with_amount(dec!(2.0), dec!(0))?;
+
+        balance.update(BalanceOperation::WithdrawAvailable(dec!(2)))?;
+
+        // The zero amounts don't turn negative.
+        assert!(balance.available().is_zero() && balance.available().is_sign_positive());
+        assert!(balance.total().is_zero() && balance.total().is_sign_positive());
+
+        Ok(())
+    }
+
+    #[test]
+    fn balance_credit_limit() -> anyhow::Result<()> {
+        let mut balance = AccountBalance::with_amount(dec!(10.0), dec!(0.0))?;
+
+        balance.update(BalanceOperation::SetCreditLimit(dec!(5.0)))?;
+        balance.update(BalanceOperation::WithdrawAvailable(dec!(12.0)))?;
+
+        assert_eq!(balance.available(), dec!(-2.0));
+        assert_eq!(balance.total(), dec!(-2.0));
+
+        assert_eq!(
+            balance.update(BalanceOperation::WithdrawAvailable(dec!(3.5))),
+            Err(BalanceOperationError::InsufficientAvailableFunds {
+                requested: dec!(3.5),
+                available: dec!(3.0)
+            })
+        );
+
+        assert!(matches!(
+            balance.update(BalanceOperation::SetCreditLimit(dec!(1.0))),
+            Err(BalanceOperationError::CreditLimitExceeded { .. })
+        ));
+
+        balance.update(BalanceOperation::Deposit(dec!(2.0)))?;
+        balance.update(BalanceOperation::SetCreditLimit(dec!(0.0)))?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,218 @@
+use crate::account::{exact_add, AccountStore};
+use crate::id::ClientId;
+use crate::transaction::{HoldStatus, StoredTransactionType, TransactionStatus, TransactionStore};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use thiserror::Error as ThisError;
+
+/// Broken ledger invariant found by [`crate::Engine::audit`].
+#[derive(Debug, ThisError, Eq, PartialEq)]
+pub enum AuditError {
+    #[error("Account {client} is unbalanced: Total={total} Available={available} Held={held}")]
+    UnbalancedAccount {
+        client: ClientId,
+        total: Decimal,
+        available: Decimal,
+        held: Decimal,
+    },
+
+    #[error(
+        "Held funds of account {client} don't match its holds: Held={held} Expected={expected}"
+    )]
+    HeldFundsMismatch {
+        client: ClientId,
+        held: Decimal,
+        expected: Decimal,
+    },
+
+    #[error("Balances don't match the transactions: Total={total} Expected={expected}")]
+    LedgerMismatch { total: Decimal, expected: Decimal },
+}
+
+/// Checks the ledger invariants, returning the first broken one:
+///  - the total funds of every account are the sum of its available and held funds;
+///  - the held funds of every account are the sum of its disputed transactions and active deposit
+///    holds;
+///  - the sum of all balances equals the credited amounts (deposits and interest) that haven't
+///    been charged back, less the withdrawals. The fees only move funds between the accounts.
+///    The sums may exceed the decimal range (or its precision) even if every balance fits into
+///    it, in which case this check is skipped.
+pub(crate) fn check_ledger(
+    accounts: &AccountStore,
+    transactions: &TransactionStore,
+) -> Result<(), AuditError> {
+    let mut held = HashMap::<ClientId, Decimal>::new();
+    let mut expected_total = Some(Decimal::ZERO);
+
+    for tx in transactions.iter() {
+        if tx.status == TransactionStatus::UnderDispute {
+            let held = held.entry(tx.account_id).or_default();
+            *held = exact_add(*held, tx.amount).unwrap_or(Decimal::MAX);
+        } else if tx.hold == Some(HoldStatus::Active) {
+            let held = held.entry(tx.account_id).or_default();
+            *held = exact_add(*held, tx.net_amount()).unwrap_or(Decimal::MAX);
+        }
+
+        let amount = match tx.ty {
+            StoredTransactionType::Withdrawal => -tx.amount,
+            _ if tx.status == TransactionStatus::Cancelled => Decimal::ZERO,
+            _ => tx.amount,
+        };
+
+        expected_total = expected_total.and_then(|total| exact_add(total, amount));
+    }
+
+    let mut total = Some(Decimal::ZERO);
+
+    for account in accounts.iter() {
+        let balance = account.balance();
+
+        if exact_add(balance.available(), balance.held()) != Some(balance.total()) {
+            return Err(AuditError::UnbalancedAccount {
+                client: account.id(),
+                total: balance.total(),
+                available: balance.available(),
+                held: balance.held(),
+            });
+        }
+
+        let expected = held.get(&account.id()).copied().unwrap_or_default();
+
+        if balance.held() != expected {
+            return Err(AuditError::HeldFundsMismatch {
+                client: account.id(),
+                held: balance.held(),
+                expected,
+            });
+        }
+
+        total = total.and_then(|total| exact_add(total, balance.total()));
+    }
+
+    if let (Some(total), Some(expected_total)) = (total, expected_total) {
+        if total != expected_total {
+            return Err(AuditError::LedgerMismatch {
+                total,
+                expected: expected_total,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::{check_ledger, AuditError};
+    use crate::account::{AccountStore, BalanceOperation};
+    use crate::config::EngineConfig;
+    use crate::engine::Engine;
+    use crate::input::InputStream;
+    use crate::transaction::{IdScope, RawTransactionData, TransactionStore, TransactionType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn consistent_ledger() -> anyhow::Result<()> {
+        let config: EngineConfig = toml::from_str(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 3
+
+            [fees]
+            house_account = 0
+
+            [fees.deposit]
+            type = "flat"
+            amount = "1.0"
+
+            [fees.withdrawal]
+            type = "percentage"
+            percent = "1"
+
+            [fees.chargeback]
+            type = "flat"
+            amount = "5.0"
+            "#,
+        )?;
+
+        let mut engine = Engine::with_config(config);
+
+        let input = InputStream::from_reader(
+            r"type, client, tx, amount
+            deposit, 1, 1, 50.0
+            deposit, 1, 2, 200.0
+            withdrawal, 1, 3, 20.0
+            dispute, 1, 2
+            deposit, 2, 4, 30.0
+            dispute, 2, 4
+            chargeback, 2, 4
+            representment, 2, 4
+            resolve, 1, 2
+            dispute, 1, 1
+            withdrawal, 1, 5, 500.0
+            deposit, 3, 6, 150.0
+            chargeback, 1, 1"
+                .as_bytes(),
+        )?;
+
+        for record in input {
+            let record = record?;
+            let _ = engine.process_transaction(&record);
+
+            engine.audit()?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn broken_ledger() -> anyhow::Result<()> {
+        let mut accounts = AccountStore::with_credit_limits(Vec::new());
+        let mut transactions = TransactionStore::new(IdScope::Global);
+
+        let deposit = RawTransactionData {
+            id: 1,
+            ty: TransactionType::Deposit,
+            account_id: 1,
+            amount: Some(dec!(10.0)),
+            timestamp: None,
+        };
+
+        transactions.insert((&deposit).try_into()?);
+
+        let balance = accounts.get_mut(1).balance_mut();
+        balance.update(BalanceOperation::Deposit(dec!(10.0)))?;
+
+        assert_eq!(check_ledger(&accounts, &transactions), Ok(()));
+
+        // Funds held without a dispute.
+        let balance = accounts.get_mut(1).balance_mut();
+        balance.update(BalanceOperation::Hold(dec!(4.0)))?;
+
+        assert_eq!(
+            check_ledger(&accounts, &transactions),
+            Err(AuditError::HeldFundsMismatch {
+                client: 1,
+                held: dec!(4.0),
+                expected: dec!(0.0),
+            })
+        );
+
+        // Funds created out of thin air.
+        let balance = accounts.get_mut(1).balance_mut();
+        balance.update(BalanceOperation::Release(dec!(4.0)))?;
+        balance.update(BalanceOperation::Deposit(dec!(1.0)))?;
+
+        assert_eq!(
+            check_ledger(&accounts, &transactions),
+            Err(AuditError::LedgerMismatch {
+                total: dec!(11.0),
+                expected: dec!(10.0),
+            })
+        );
+
+        Ok(())
+    }
+}
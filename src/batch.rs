@@ -0,0 +1,272 @@
+use crate::id::TransactionId;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Records the prior state of every entry changed since it was started, so that the changes can
+/// be rolled back.
+#[derive(Clone)]
+pub(crate) struct Journal<K, V> {
+    entries: Option<HashMap<K, Option<V>>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Journal<K, V> {
+    pub fn new() -> Self {
+        Self { entries: None }
+    }
+
+    /// Starts recording the changes, discarding any previous records.
+    pub fn start(&mut self) {
+        self.entries = Some(HashMap::new());
+    }
+
+    /// Stops recording the changes, keeping them.
+    pub fn commit(&mut self) {
+        self.entries = None;
+    }
+
+    /// Records the state of the entry about to be changed, unless it's already been recorded.
+    #[inline]
+    pub fn record(&mut self, key: K, current: Option<&V>) {
+        if let Some(entries) = &mut self.entries {
+            entries.entry(key).or_insert_with(|| current.cloned());
+        }
+    }
+
+    /// Stops recording the changes, restoring the recorded entries in the data.
+    pub fn rollback(&mut self, data: &mut HashMap<K, V>) {
+        for (key, value) in self.entries.take().into_iter().flatten() {
+            match value {
+                Some(value) => data.insert(key, value),
+                None => data.remove(&key),
+            };
+        }
+    }
+}
+
+/// Batch of transactions applied atomically, opened by a `begin` row and closed by a `commit` row.
+#[derive(Clone)]
+pub(crate) struct Batch {
+    pub id: TransactionId,
+
+    /// Number of the rows processed within the batch so far.
+    pub rows: usize,
+
+    /// Number of the row within the batch that failed and rolled it back, if any.
+    pub failed: Option<usize>,
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use crate::account::AccountBalance;
+    use crate::engine::{Engine, EngineError, TransactionOutcome, ValidationError};
+    use crate::testing::{account_balance, create_engine, create_input};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn committed_batch() -> anyhow::Result<()> {
+        let mut engine = Engine::new();
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            begin, 1, 100,
+            withdrawal, 1, 2, 4.0
+            deposit, 2, 3, 4.0
+            commit, 1, 100,",
+        );
+
+        for data in &input {
+            assert_eq!(
+                engine.process_transaction(data)?,
+                TransactionOutcome::Applied
+            );
+        }
+
+        engine.finish()?;
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(6.0), dec!(0.0))?
+        );
+        assert_eq!(
+            account_balance(&engine, 2),
+            AccountBalance::with_amount(dec!(4.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rolled_back_batch() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [[rules]]
+            type = "velocity"
+            window = 10
+            max_count = 2
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            begin, 1, 100,
+            withdrawal, 1, 2, 4.0
+            deposit, 2, 3, 4.0
+            dispute, 1, 1
+            withdrawal, 1, 4, 1.0
+            commit, 1, 100,
+            withdrawal, 1, 5, 1.0
+            withdrawal, 1, 2, 1.0
+            deposit, 2, 3, 1.0",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+        next()?;
+        next()?;
+        next()?;
+
+        // The dispute fails, since the withdrawal within the batch left too little funds.
+        assert!(matches!(
+            next(),
+            Err(EngineError::BatchRolledBack { batch: 100, row: 3, source })
+                if matches!(*source, EngineError::BalanceOperationError(_))
+        ));
+
+        assert!(matches!(
+            next(),
+            Err(EngineError::BatchAborted { batch: 100, row: 3 })
+        ));
+        assert!(matches!(
+            next(),
+            Err(EngineError::BatchAborted { batch: 100, row: 3 })
+        ));
+
+        // Neither the withdrawal nor the deposit in the batch count, so their IDs can be reused,
+        // and the withdrawal isn't counted by the velocity rule.
+        next()?;
+        next()?;
+        next()?;
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(8.0), dec!(0.0))?
+        );
+        assert_eq!(
+            account_balance(&engine, 2),
+            AccountBalance::with_amount(dec!(1.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rolled_back_hold_release() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 1
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 150.0
+            begin, 1, 100,
+            withdrawal, 1, 2, 50.0
+            deposit, 1, 3, 200.0
+            withdrawal, 1, 4, 1000.0
+            commit, 1, 100,
+            withdrawal, 1, 5, 50.0",
+        );
+
+        for tx in &input[..4] {
+            // The first deposit is released within the batch, and the second one is held.
+            engine.process_transaction(tx)?;
+        }
+
+        assert!(matches!(
+            engine.process_transaction(&input[4]),
+            Err(EngineError::BatchRolledBack {
+                batch: 100,
+                row: 3,
+                ..
+            })
+        ));
+        assert!(matches!(
+            engine.process_transaction(&input[5]),
+            Err(EngineError::BatchAborted { batch: 100, row: 3 })
+        ));
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(150.0), dec!(150.0))?
+        );
+
+        // The release is rolled back as well, so the first deposit is released again.
+        engine.process_transaction(&input[6])?;
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(100.0), dec!(0.0))?
+        );
+        assert!(engine.audit().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_errors() -> anyhow::Result<()> {
+        let mut engine = Engine::new();
+
+        let input = create_input(
+            r"type, client, tx, amount
+            commit, 1, 100,
+            begin, 1, 100,
+            begin, 1, 101,
+            commit, 1, 101,
+            deposit, 1, 1, 10.0",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::NoOpenBatch
+            ))
+        ));
+
+        next()?;
+
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::BatchAlreadyOpen
+            ))
+        ));
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::InvalidTransactionId
+            ))
+        ));
+
+        next()?;
+
+        // The batch is never committed.
+        assert!(matches!(
+            engine.finish(),
+            Err(EngineError::BatchNotCommitted { batch: 100 })
+        ));
+        assert!(engine.account(1).is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+use crate::metrics::Metrics;
+use anyhow::{bail, Context};
+use clap::{arg, Arg, ArgMatches};
+use std::fmt;
+use std::io;
+use std::num::NonZeroUsize;
+use std::time::Instant;
+use tracing::{field, warn_span};
+use tx_toy::{
+    AuditError, Engine, EngineConfig, EngineResult, InputSchema, InputStream, RawTransactionData,
+    RecordPosition, TransactionOutcome,
+};
+
+/// Input path reading from `stdin`.
+const STDIN_PATH: &str = "-";
+
+/// Where the ledger audit at the end of the inputs is reported.
+const END_OF_INPUTS: &str = "the end of the inputs";
+
+/// The input files argument shared by all of the commands reading transactions.
+pub fn input_arg() -> Arg<'static> {
+    arg!(<INPUT_FILE> ... "Paths to input CSV files or glob patterns, processed in order; `-` reads from stdin")
+}
+
+/// Input decoding options shared by all of the commands reading transactions.
+pub fn input_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--strict "Reject missing or unknown headers, extra columns and unknown transaction types"),
+        arg!(--schema <SCHEMA_FILE> "Path to input column mapping TOML/JSON file").required(false),
+    ]
+}
+
+/// Arguments shared by all of the commands running the engine.
+pub fn engine_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(-c --config <CONFIG_FILE> "Path to engine configuration TOML/JSON file")
+            .required(false),
+        arg!(--audit <N> "Check the ledger invariants after every N rows and at the end")
+            .required(false)
+            .validator(|value| value.parse::<NonZeroUsize>()),
+        arg!(--metrics <ADDR> "Serve Prometheus metrics on http://ADDR/metrics while processing, e.g. 127.0.0.1:9100")
+            .required(false),
+    ]
+}
+
+/// Creates an engine as configured by the command arguments.
+pub fn create_engine(args: &ArgMatches) -> anyhow::Result<Engine> {
+    let config = match args.value_of("config") {
+        Some(path) => EngineConfig::from_file(path).context("Failed to load configuration")?,
+        None => EngineConfig::default(),
+    };
+
+    Ok(Engine::with_config(config))
+}
+
+/// Returns the input paths specified by the command arguments, with the glob patterns expanded.
+pub fn input_paths(args: &ArgMatches) -> anyhow::Result<Vec<String>> {
+    let mut paths = Vec::new();
+
+    for path in args.values_of("INPUT_FILE").into_iter().flatten() {
+        // Shells expand the patterns themselves, but quoted patterns allow processing more files
+        // than the command line length limit allows.
+        if path == STDIN_PATH || !path.contains(['*', '?', '[']) {
+            paths.push(path.to_owned());
+            continue;
+        }
+
+        let len = paths.len();
+
+        for entry in glob::glob(path).context("Invalid input file pattern")? {
+            paths.push(entry?.display().to_string());
+        }
+
+        if paths.len() == len {
+            bail!("No input files match the pattern: {path}");
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Loads the input schema specified by the command arguments.
+pub fn input_schema(args: &ArgMatches) -> anyhow::Result<InputSchema> {
+    match args.value_of("schema") {
+        Some(path) => InputSchema::from_file(path).context("Failed to load input schema"),
+        None => Ok(InputSchema::default()),
+    }
+}
+
+/// Opens the input stream for the path, `-` being `stdin`, as configured by the command arguments.
+pub fn open_input(
+    path: &str,
+    schema: &InputSchema,
+    args: &ArgMatches,
+) -> anyhow::Result<InputStream> {
+    let mut input = if path == STDIN_PATH {
+        InputStream::from_reader_with_schema(io::stdin(), schema.clone())
+            .map(|input| input.with_name("<stdin>"))
+    } else {
+        InputStream::from_file_with_schema(path, schema.clone())
+    };
+
+    if args.is_present("strict") {
+        input = input.and_then(InputStream::strict);
+    }
+
+    input.with_context(|| format!("Failed to create input stream: {path}"))
+}
+
+/// A processed input row.
+pub struct Row<'a> {
+    /// Name of the input the row was read from.
+    pub source: &'a str,
+
+    /// Position of the row in the input.
+    pub position: RecordPosition,
+
+    /// The row data, not available if it couldn't be decoded.
+    pub data: Option<&'a RawTransactionData>,
+}
+
+impl fmt::Display for Row<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.position.line)
+    }
+}
+
+/// Processes all transactions from the input files specified by the command arguments in order,
+/// passing the result of every row to the callback along with the engine state after it.
+/// Bad transactions do not stop the processing, while the inputs that couldn't be opened do.
+///
+/// A batch left open at the end of the inputs is rolled back, and reported as an error of the last
+/// row.
+///
+/// With `--audit <N>`, the ledger invariants are checked after every N rows and at the end, and
+/// the processing stops at the first failed check.
+///
+/// With `--metrics <ADDR>`, the processing metrics are served on the address until the function
+/// returns.
+pub fn process_inputs<F>(engine: &mut Engine, args: &ArgMatches, callback: F) -> anyhow::Result<()>
+where
+    F: FnMut(&Engine, &Row, EngineResult<TransactionOutcome>),
+{
+    process_inputs_until(engine, args, None, callback)
+}
+
+/// Same as [`process_inputs`], but stops after the first `limit` rows of the inputs (counting the
+/// rows that couldn't be decoded), if the limit is set. The inputs are then finished as if they
+/// ended there.
+pub fn process_inputs_until<F>(
+    engine: &mut Engine,
+    args: &ArgMatches,
+    limit: Option<usize>,
+    mut callback: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&Engine, &Row, EngineResult<TransactionOutcome>),
+{
+    let schema = input_schema(args)?;
+    let mut last = (String::new(), RecordPosition::default());
+    let mut rows = 0;
+
+    let mut audit = Audit::new(args.value_of_t::<NonZeroUsize>("audit").ok(), engine);
+
+    let mut metrics = args.value_of("metrics").map(Metrics::serve).transpose()?;
+
+    for path in input_paths(args)? {
+        let mut input = open_input(&path, &schema, args)?;
+
+        while limit.is_none_or(|limit| rows < limit) {
+            let Some(data) = input.next() else {
+                break;
+            };
+
+            rows += 1;
+
+            // The row's span carries its position and IDs to everything logged while it's
+            // processed, including the callback. It's enabled along with the rejection warnings.
+            let span = warn_span!(
+                "row",
+                source = input.name(),
+                line = input.position().line,
+                tx = field::Empty,
+                client = field::Empty,
+                r#type = field::Empty,
+            );
+
+            if let Ok(data) = &data {
+                span.record("tx", field::display(data.id));
+                span.record("client", field::display(data.account_id));
+                span.record("type", data.ty.name());
+            }
+
+            let _span = span.enter();
+            let started = Instant::now();
+
+            let (data, res) = match data {
+                Ok(data) => {
+                    let res = engine.process_transaction(&data);
+                    (Some(data), res)
+                }
+
+                Err(err) => (None, Err(err.into())),
+            };
+
+            if let Some(metrics) = &mut metrics {
+                metrics.row(
+                    engine,
+                    data.as_deref(),
+                    res.as_ref().map(|_| ()),
+                    started.elapsed(),
+                );
+            }
+
+            let row = Row {
+                source: input.name(),
+                position: input.position(),
+                data: data.as_ref().map(|record| &record.data),
+            };
+
+            callback(engine, &row, res);
+            audit.row(engine, &row)?;
+        }
+
+        last = (input.name().to_owned(), input.position());
+
+        if limit.is_some_and(|limit| rows >= limit) {
+            break;
+        }
+    }
+
+    let span = warn_span!("row", source = last.0, line = last.1.line).entered();
+
+    if let Err(err) = engine.finish() {
+        let row = Row {
+            source: &last.0,
+            position: last.1,
+            data: None,
+        };
+
+        if let Some(metrics) = &mut metrics {
+            metrics.error(&err);
+        }
+
+        callback(engine, &row, Err(err));
+    }
+
+    span.exit();
+
+    if let Some(metrics) = &mut metrics {
+        metrics.refresh(engine);
+    }
+
+    audit.finish(engine)
+}
+
+/// Periodic ledger audit of the processed inputs.
+struct Audit {
+    /// Number of rows between the checks, disabled if not set.
+    interval: Option<NonZeroUsize>,
+
+    /// Number of rows processed so far.
+    rows: usize,
+
+    /// Engine state as of the last passed check, and the rows processed since. A failed check
+    /// replays them to find the row that broke the invariants.
+    ///
+    /// Copying the engine costs about as much as the check itself, which walks the whole ledger.
+    checkpoint: Option<Engine>,
+    pending: Vec<(String, RawTransactionData)>,
+}
+
+impl Audit {
+    fn new(interval: Option<NonZeroUsize>, engine: &Engine) -> Self {
+        Self {
+            interval,
+            rows: 0,
+            checkpoint: interval.map(|_| engine.clone()),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Checks the ledger if the row is due for a check.
+    fn row(&mut self, engine: &Engine, row: &Row) -> anyhow::Result<()> {
+        let Some(interval) = self.interval else {
+            return Ok(());
+        };
+
+        self.rows += 1;
+
+        // The rows that couldn't be decoded never reach the engine.
+        if let Some(data) = row.data {
+            self.pending.push((row.to_string(), data.clone()));
+        }
+
+        if self.rows.is_multiple_of(interval.get()) {
+            self.check(engine, &row.to_string(), false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the ledger at the end of the inputs, after the engine has been finished.
+    fn finish(&mut self, engine: &Engine) -> anyhow::Result<()> {
+        match self.interval {
+            Some(_) => self.check(engine, END_OF_INPUTS, true),
+            None => Ok(()),
+        }
+    }
+
+    fn check(&mut self, engine: &Engine, at: &str, finished: bool) -> anyhow::Result<()> {
+        if let Err(err) = engine.audit() {
+            match self.find_breaking_row(finished) {
+                Some((row, err)) => {
+                    bail!("Ledger audit failed after {at}, first broken by {row}: {err}")
+                }
+                None => bail!("Ledger audit failed after {at}: {err}"),
+            }
+        }
+
+        self.checkpoint = Some(engine.clone());
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    /// Replays the rows since the last passed check, checking the ledger after every one of them,
+    /// and returns the first row that fails the check along with the error.
+    fn find_breaking_row(&mut self, finished: bool) -> Option<(String, AuditError)> {
+        let mut engine = self.checkpoint.take()?;
+
+        for (row, data) in self.pending.drain(..) {
+            // The outcomes have already been reported.
+            let _ = engine.process_transaction(&data);
+
+            if let Err(err) = engine.audit() {
+                return Some((row, err));
+            }
+        }
+
+        if finished {
+            let _ = engine.finish();
+
+            if let Err(err) = engine.audit() {
+                return Some((END_OF_INPUTS.to_owned(), err));
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,37 @@
+use crate::EXIT_SUCCESS;
+use anyhow::Context;
+use clap::{arg, ArgMatches, Command};
+use tx_toy::{Snapshot, SnapshotDiff};
+
+pub fn command() -> Command<'static> {
+    Command::new("diff")
+        .about("Compares two account dumps or engine snapshots")
+        .long_about(
+            "Compares two account dumps (CSV, as printed by `process`) or engine snapshots (JSON, \
+             as written by `process --snapshot`), reporting the added and removed clients, the \
+             balance deltas, the lock state changes and, for snapshots, the transaction status \
+             changes.",
+        )
+        .arg(arg!(<OLD_FILE> "Path to the old account dump or snapshot"))
+        .arg(arg!(<NEW_FILE> "Path to the new account dump or snapshot"))
+        .arg(arg!(--json "Print the differences as JSON"))
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let old = load(args.value_of("OLD_FILE").unwrap_or_default())?;
+    let new = load(args.value_of("NEW_FILE").unwrap_or_default())?;
+
+    let diff = SnapshotDiff::new(&old, &new)?;
+
+    if args.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        print!("{diff}");
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn load(path: &str) -> anyhow::Result<Snapshot> {
+    Snapshot::from_file(path).with_context(|| format!("Failed to load snapshot: {path}"))
+}
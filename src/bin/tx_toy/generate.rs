@@ -0,0 +1,125 @@
+use crate::EXIT_SUCCESS;
+use anyhow::Context;
+use clap::{arg, ArgMatches, Command};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use tx_toy::{ClientId, Generator, TransactionId, TransactionType, TypeMix, WorkloadConfig};
+
+pub fn command() -> Command<'static> {
+    Command::new("generate")
+        .about("Generates a synthetic transaction workload as CSV or JSON Lines")
+        .long_about(
+            "Generates a synthetic transaction workload for benchmarks and load tests. The rows \
+             are valid for the default engine configuration, with the disputes, resolutions and \
+             chargebacks referencing earlier deposits, except for the requested fraction of \
+             invalid rows. The same options and seed generate the same rows.",
+        )
+        .arg(
+            arg!(-n --rows <N> "Number of rows to generate")
+                .required(false)
+                .default_value("1000")
+                .validator(|value| value.parse::<u64>()),
+        )
+        .arg(
+            arg!(--clients <M> "Number of clients, with the IDs from 1 to M")
+                .required(false)
+                .default_value("10")
+                .validator(|value| value.parse::<u64>()),
+        )
+        .arg(
+            arg!(--mix <MIX> "Relative weights of the transaction types [default: deposit=60,withdrawal=30,dispute=5,resolve=4,chargeback=1]")
+                .required(false)
+                .validator(|value| value.parse::<TypeMix>()),
+        )
+        .arg(
+            arg!(--invalid <FRACTION> "Fraction of the rows to be rejected by the engine, from 0 to 1")
+                .required(false)
+                .default_value("0")
+                .validator(|value| match value.parse::<f64>() {
+                    Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(()),
+                    _ => Err("Expected a number from 0 to 1"),
+                }),
+        )
+        .arg(
+            arg!(--seed <SEED> "Seed of the random number generator")
+                .required(false)
+                .default_value("0")
+                .validator(|value| value.parse::<u64>()),
+        )
+        .arg(
+            arg!(--format <FORMAT> "Output format")
+                .required(false)
+                .possible_values(["csv", "jsonl"])
+                .default_value("csv"),
+        )
+        .arg(arg!(-o --output <OUTPUT_FILE> "Path to write the rows to, instead of stdout").required(false))
+}
+
+/// Generated row, with the columns in the order of the standard input headers.
+#[derive(Serialize)]
+struct Row {
+    #[serde(rename = "type")]
+    ty: TransactionType,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<Decimal>,
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    // The values are checked by the validators.
+    let config = WorkloadConfig {
+        rows: args.value_of_t("rows")?,
+        clients: args.value_of_t("clients")?,
+        mix: args.value_of_t("mix").unwrap_or_default(),
+        invalid_fraction: args.value_of_t("invalid")?,
+        seed: args.value_of_t("seed")?,
+    };
+
+    let generator = Generator::new(config)?;
+
+    let output: Box<dyn Write> = match args.value_of("output") {
+        Some(path) => Box::new(
+            File::create(path).with_context(|| format!("Failed to create output: {path}"))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let rows = generator.map(|data| Row {
+        ty: data.ty,
+        client: data.account_id,
+        tx: data.id,
+        amount: data.amount,
+    });
+
+    match args.value_of("format") {
+        Some("jsonl") => write_jsonl(BufWriter::new(output), rows)?,
+        _ => write_csv(output, rows)?,
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn write_csv<W: Write>(output: W, rows: impl Iterator<Item = Row>) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(output);
+
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_jsonl<W: Write>(mut output: W, rows: impl Iterator<Item = Row>) -> anyhow::Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut output, &row)?;
+        output.write_all(b"\n")?;
+    }
+
+    output.flush()?;
+
+    Ok(())
+}
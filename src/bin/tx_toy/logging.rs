@@ -0,0 +1,53 @@
+use clap::{arg, Arg, ArgMatches};
+use std::io::IsTerminal;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+/// Logging options, accepted by every command.
+pub fn args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(-v --verbose ... "Log more: once for every processed row, twice for everything")
+            .global(true),
+        arg!(-q --quiet ... "Log less: once for problems only, twice for errors only, thrice for nothing")
+            .global(true),
+        arg!(--"log-format" <FORMAT> "Log output format")
+            .required(false)
+            .possible_values(["text", "json"])
+            .default_value("text")
+            .global(true),
+    ]
+}
+
+/// Sets up logging to `stderr` as configured by the command arguments. `RUST_LOG`, if set,
+/// overrides the verbosity options, e.g. `RUST_LOG=tx_toy=debug`.
+pub fn init(args: &ArgMatches) {
+    let levels = [
+        LevelFilter::OFF,
+        LevelFilter::ERROR,
+        LevelFilter::WARN,
+        LevelFilter::INFO,
+        LevelFilter::DEBUG,
+        LevelFilter::TRACE,
+    ];
+
+    // Info by default.
+    let level = (3 + args.occurrences_of("verbose") as usize)
+        .saturating_sub(args.occurrences_of("quiet") as usize)
+        .min(levels.len() - 1);
+
+    let filter = EnvFilter::builder()
+        .with_default_directive(levels[level].into())
+        .from_env_lossy();
+
+    // Colors only make sense on a terminal, not in redirected logs.
+    let logger = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_env_filter(filter);
+
+    if args.value_of("log-format") == Some("json") {
+        logger.json().init();
+    } else {
+        logger.init();
+    }
+}
@@ -0,0 +1,65 @@
+mod common;
+mod diff;
+mod generate;
+mod logging;
+mod metrics;
+mod process;
+mod reconcile;
+mod replay;
+mod statement;
+mod stats;
+mod validate;
+
+use clap::command;
+use std::process::ExitCode;
+
+/// Exit code for successful runs.
+pub const EXIT_SUCCESS: u8 = 0;
+
+/// Exit code for runs that completed, but found problems with the data, e.g. invalid rows.
+pub const EXIT_INVALID_DATA: u8 = 3;
+
+// Fatal errors (I/O, configuration) exit with 1, and usage errors exit with 2.
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args = command!()
+        .arg_required_else_help(true)
+        .args_conflicts_with_subcommands(true)
+        .after_help(
+            "Exit codes: 0 on success, 1 on fatal errors, 2 on usage errors, 3 if the data has \
+             problems (e.g. invalid rows found by `validate`, or balance differences found by \
+             `reconcile`).",
+        )
+        // Kept for compatibility: `tx_toy <INPUT_FILE>` is the same as `tx_toy process <INPUT_FILE>`.
+        .arg(common::input_arg().required(false))
+        .args(common::input_args())
+        .args(common::engine_args())
+        .args(process::args())
+        .args(logging::args())
+        .subcommand(process::command())
+        .subcommand(validate::command())
+        .subcommand(stats::command())
+        .subcommand(statement::command())
+        .subcommand(replay::command())
+        .subcommand(reconcile::command())
+        .subcommand(diff::command())
+        .subcommand(generate::command())
+        .get_matches();
+
+    // The global options are propagated to the subcommand.
+    logging::init(args.subcommand().map_or(&args, |(_, args)| args));
+
+    let code = match args.subcommand() {
+        Some(("process", args)) => process::run(args)?,
+        Some(("validate", args)) => validate::run(args)?,
+        Some(("stats", args)) => stats::run(args)?,
+        Some(("statement", args)) => statement::run(args)?,
+        Some(("replay", args)) => replay::run(args)?,
+        Some(("reconcile", args)) => reconcile::run(args)?,
+        Some(("diff", args)) => diff::run(args)?,
+        Some(("generate", args)) => generate::run(args)?,
+        _ => process::run(&args)?,
+    };
+
+    Ok(ExitCode::from(code))
+}
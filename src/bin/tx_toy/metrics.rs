@@ -0,0 +1,167 @@
+use anyhow::anyhow;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use rust_decimal::prelude::ToPrimitive;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+use tx_toy::{Engine, EngineError, RawTransactionData, TransactionStatus};
+
+/// The gauges are updated by walking all of the accounts and transactions, once the number of
+/// rows since the last update reaches the number of the walked entries divided by this ratio. The
+/// amortized cost per row is therefore constant, while the small states are updated on every row.
+const REFRESH_RATIO: usize = 64;
+
+/// Processing metrics, served in the Prometheus text format on the `/metrics` endpoint.
+pub struct Metrics {
+    rows: IntCounterVec,
+    rejected: IntCounterVec,
+
+    /// Errors not caused by an input row, e.g. a batch left open at the end of the inputs.
+    errors: IntCounterVec,
+
+    latency: Histogram,
+
+    accounts: IntGauge,
+    locked_accounts: IntGauge,
+    open_disputes: IntGauge,
+    held_funds: Gauge,
+
+    /// Rows processed since the last gauge update.
+    pending: usize,
+
+    /// Number of the entries walked by the last gauge update.
+    cost: usize,
+}
+
+impl Metrics {
+    /// Registers the metrics and starts serving them on the address in a background thread.
+    pub fn serve(addr: &str) -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("tx_toy".to_owned()), None)?;
+
+        let metrics = Self {
+            rows: IntCounterVec::new(
+                Opts::new("rows_processed_total", "Processed input rows"),
+                &["type"],
+            )?,
+            rejected: IntCounterVec::new(
+                Opts::new("rows_rejected_total", "Rejected input rows"),
+                &["type", "error"],
+            )?,
+            errors: IntCounterVec::new(
+                Opts::new("errors_total", "Errors not caused by an input row"),
+                &["error"],
+            )?,
+            latency: Histogram::with_opts(
+                HistogramOpts::new("processing_seconds", "Transaction processing latency")
+                    .buckets(prometheus::exponential_buckets(1e-6, 4.0, 10)?),
+            )?,
+            accounts: IntGauge::new("accounts", "Client accounts")?,
+            locked_accounts: IntGauge::new("locked_accounts", "Locked client accounts")?,
+            open_disputes: IntGauge::new("open_disputes", "Transactions under dispute")?,
+            held_funds: Gauge::new("held_funds", "Held funds of all client accounts")?,
+            pending: 0,
+            cost: 0,
+        };
+
+        registry.register(Box::new(metrics.rows.clone()))?;
+        registry.register(Box::new(metrics.rejected.clone()))?;
+        registry.register(Box::new(metrics.errors.clone()))?;
+        registry.register(Box::new(metrics.latency.clone()))?;
+        registry.register(Box::new(metrics.accounts.clone()))?;
+        registry.register(Box::new(metrics.locked_accounts.clone()))?;
+        registry.register(Box::new(metrics.open_disputes.clone()))?;
+        registry.register(Box::new(metrics.held_funds.clone()))?;
+
+        let server = Server::http(addr)
+            .map_err(|err| anyhow!("Failed to start metrics endpoint on {addr}: {err}"))?;
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = if request.url() == "/metrics" {
+                    let encoder = TextEncoder::new();
+                    let mut body = Vec::new();
+
+                    // Encoding the text format doesn't fail.
+                    let _ = encoder.encode(&registry.gather(), &mut body);
+
+                    let content_type = Header::from_bytes("Content-Type", encoder.format_type())
+                        .expect("Valid content type header");
+
+                    Response::from_data(body).with_header(content_type)
+                } else {
+                    Response::from_string("Not found").with_status_code(404)
+                };
+
+                // The client may have gone away already.
+                let _ = request.respond(response);
+            }
+        });
+
+        Ok(metrics)
+    }
+
+    /// Records a processed row. Rows that couldn't be decoded have no data.
+    pub fn row(
+        &mut self,
+        engine: &Engine,
+        data: Option<&RawTransactionData>,
+        res: Result<(), &EngineError>,
+        elapsed: Duration,
+    ) {
+        let ty = data.map_or("unknown", |data| data.ty.name());
+
+        self.rows.with_label_values(&[ty]).inc();
+
+        if let Err(err) = res {
+            self.rejected.with_label_values(&[ty, err.kind()]).inc();
+        }
+
+        if data.is_some() {
+            self.latency.observe(elapsed.as_secs_f64());
+        }
+
+        self.pending += 1;
+
+        if self.pending >= self.cost / REFRESH_RATIO {
+            self.refresh(engine);
+        }
+    }
+
+    /// Records an error not caused by an input row.
+    pub fn error(&mut self, err: &EngineError) {
+        self.errors.with_label_values(&[err.kind()]).inc();
+    }
+
+    /// Updates the gauges from the engine state.
+    pub fn refresh(&mut self, engine: &Engine) {
+        let mut accounts = 0;
+        let mut locked = 0;
+        // Summed as `f64`, like the gauge, since the sum of the held balances can exceed the
+        // decimal range.
+        let mut held = 0.0;
+
+        for account in engine.accounts() {
+            accounts += 1;
+            locked += i64::from(account.locked());
+            held += account.balance().held().to_f64().unwrap_or_default();
+        }
+
+        let mut transactions = 0;
+        let mut disputes = 0;
+
+        for tx in engine.transactions() {
+            transactions += 1;
+            disputes += i64::from(tx.status == TransactionStatus::UnderDispute);
+        }
+
+        self.accounts.set(accounts);
+        self.locked_accounts.set(locked);
+        self.open_disputes.set(disputes);
+        self.held_funds.set(held);
+
+        self.pending = 0;
+        self.cost = accounts as usize + transactions;
+    }
+}
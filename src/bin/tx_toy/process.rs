@@ -0,0 +1,75 @@
+use crate::common;
+use crate::EXIT_SUCCESS;
+use anyhow::Context;
+use clap::{arg, Arg, ArgMatches, Command};
+use std::fs::File;
+use std::io::BufWriter;
+use tracing::{debug, info, warn};
+use tx_toy::{AccountInfo, Engine, EngineError, EngineResult, TransactionOutcome};
+
+pub fn command() -> Command<'static> {
+    Command::new("process")
+        .about("Processes transactions and prints the resulting account balances as CSV")
+        .arg(common::input_arg())
+        .args(common::input_args())
+        .args(common::engine_args())
+        .args(args())
+}
+
+/// Options of the `process` command, also accepted by the top-level shortcut for it.
+pub fn args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--snapshot <SNAPSHOT_FILE> "Path to write the engine snapshot JSON to")
+            .required(false),
+    ]
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let mut engine = common::create_engine(args)?;
+
+    common::process_inputs(&mut engine, args, |_, _, res| log_result(res))?;
+
+    dump_account_data(&engine)?;
+
+    if let Some(path) = args.value_of("snapshot") {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create snapshot: {path}"))?;
+
+        engine
+            .snapshot()
+            .write_json(BufWriter::new(file))
+            .with_context(|| format!("Failed to write snapshot: {path}"))?;
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+/// Logs the result of a row. The rows are logged within their spans, see
+/// `common::process_inputs`.
+pub fn log_result(res: EngineResult<TransactionOutcome>) {
+    match res {
+        Ok(TransactionOutcome::Applied) => debug!("Applied"),
+        Ok(TransactionOutcome::Duplicate) => info!("Duplicate, skipped"),
+        Ok(TransactionOutcome::Skipped) => info!("Account locked, skipped"),
+        Ok(TransactionOutcome::Shortfall(amount)) => {
+            warn!(shortfall = %amount, "Applied with shortfall")
+        }
+        Err(err @ EngineError::DecodingError(_)) => {
+            warn!(error = %err, "Error decoding transaction")
+        }
+        Err(err) => warn!(error = %err, kind = err.kind(), "Rejected"),
+    }
+}
+
+/// Dumps account data to `stdout`.
+pub fn dump_account_data(engine: &Engine) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    for account in engine.accounts() {
+        writer.serialize(AccountInfo::from(account))?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::common;
+use crate::{EXIT_INVALID_DATA, EXIT_SUCCESS};
+use anyhow::{bail, Context};
+use clap::{arg, ArgMatches, Command};
+use std::collections::HashSet;
+use tx_toy::reconcile::reconcile;
+use tx_toy::AccountInfo;
+
+pub fn command() -> Command<'static> {
+    Command::new("reconcile")
+        .about("Processes transactions and compares the account balances to the expected ones")
+        .long_about(
+            "Processes transactions and compares the resulting account balances to the expected \
+             balances file, in the same CSV format as the output of `process`. Reports the \
+             missing and unexpected clients and every mismatching field. Exits with 3 if there \
+             are any differences.",
+        )
+        .arg(arg!(-e --expected <BALANCES_FILE> "Path to the expected account balances CSV file"))
+        .arg(common::input_arg())
+        .args(common::input_args())
+        .args(common::engine_args())
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    // Loaded first, so that a bad file is reported before processing the inputs.
+    let path = args.value_of("expected").unwrap_or_default();
+    let expected =
+        load_balances(path).with_context(|| format!("Failed to load expected balances: {path}"))?;
+
+    let mut engine = common::create_engine(args)?;
+
+    // The rows themselves are not reported, only their effect on the balances.
+    common::process_inputs(&mut engine, args, |_, _, _| {})?;
+
+    let discrepancies = reconcile(engine.accounts().map(AccountInfo::from), &expected);
+
+    for discrepancy in &discrepancies {
+        println!("{discrepancy}");
+    }
+
+    println!(
+        "{} clients expected, {} discrepancies",
+        expected.len(),
+        discrepancies.len()
+    );
+
+    Ok(if discrepancies.is_empty() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_INVALID_DATA
+    })
+}
+
+/// Loads the account balances from a CSV file.
+fn load_balances(path: &str) -> anyhow::Result<Vec<AccountInfo>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut balances = Vec::new();
+    let mut clients = HashSet::new();
+
+    for info in reader.deserialize() {
+        let info: AccountInfo = info?;
+
+        if !clients.insert(info.client) {
+            bail!("Duplicate client: {}", info.client);
+        }
+
+        balances.push(info);
+    }
+
+    Ok(balances)
+}
@@ -0,0 +1,35 @@
+use crate::common;
+use crate::process;
+use crate::EXIT_SUCCESS;
+use clap::{arg, ArgMatches, Command};
+
+pub fn command() -> Command<'static> {
+    Command::new("replay")
+        .about("Processes the first N transactions and prints the account balances as of then")
+        .long_about(
+            "Processes the first N rows of the inputs and prints the account balances at that \
+             point as CSV, in the `process` output format, e.g. to find out the state the \
+             accounts were in before a given row.\n\nThe rows are counted across all of the \
+             inputs, including the ones that couldn't be decoded. A batch still open after the \
+             N-th row is rolled back, as it hasn't been committed by then.",
+        )
+        .arg(common::input_arg())
+        .arg(
+            arg!(--until <N> "Number of rows to process").validator(|value| value.parse::<usize>()),
+        )
+        .args(common::input_args())
+        .args(common::engine_args())
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let mut engine = common::create_engine(args)?;
+    let until = args.value_of_t::<usize>("until")?;
+
+    common::process_inputs_until(&mut engine, args, Some(until), |_, _, res| {
+        process::log_result(res)
+    })?;
+
+    process::dump_account_data(&engine)?;
+
+    Ok(EXIT_SUCCESS)
+}
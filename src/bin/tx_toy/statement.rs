@@ -0,0 +1,102 @@
+use crate::common;
+use crate::EXIT_SUCCESS;
+use clap::{arg, ArgMatches, Command};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tx_toy::{AccountInfo, ClientId, TransactionId, TransactionOutcome, TransactionType};
+
+/// A row of the client's statement: the input row, its outcome and the balance after it.
+#[derive(Serialize)]
+struct StatementRow {
+    /// The input file name and line number.
+    row: String,
+
+    #[serde(rename = "type")]
+    ty: TransactionType,
+
+    tx: TransactionId,
+    amount: Option<Decimal>,
+
+    /// `applied`, `duplicate`, `skipped`, `shortfall`, or the error kind of a rejected row.
+    outcome: &'static str,
+
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: bool,
+}
+
+pub fn command() -> Command<'static> {
+    Command::new("statement")
+        .about("Processes transactions and prints the statement of a client as CSV")
+        .long_about(
+            "Processes transactions and prints the statement of a client as CSV: every row of \
+             the client with its outcome (`applied`, `duplicate`, `skipped`, `shortfall`, or the \
+             error kind of a rejected row) and the account balance right after it.\n\nThe \
+             balances are the ones at the time of the row, so the rows of a batch rolled back \
+             later are listed with the balances they had before the rollback. Fees credited to \
+             the house account by the rows of other clients aren't listed.",
+        )
+        .arg(common::input_arg())
+        .arg(
+            arg!(--client <ID> "ID of the client to print the statement of")
+                .validator(|value| value.parse::<ClientId>()),
+        )
+        .args(common::input_args())
+        .args(common::engine_args())
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let mut engine = common::create_engine(args)?;
+    let client = args.value_of_t::<ClientId>("client")?;
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let mut res = Ok(());
+
+    common::process_inputs(&mut engine, args, |engine, row, outcome| {
+        let data = match row.data {
+            Some(data) if data.account_id == client => data,
+            _ => return,
+        };
+
+        let outcome = match outcome {
+            Ok(TransactionOutcome::Applied) => "applied",
+            Ok(TransactionOutcome::Duplicate) => "duplicate",
+            Ok(TransactionOutcome::Skipped) => "skipped",
+            Ok(TransactionOutcome::Shortfall(_)) => "shortfall",
+            Err(err) => err.kind(),
+        };
+
+        // The account doesn't exist until its first accepted row.
+        let balance = engine
+            .account(client)
+            .map(AccountInfo::from)
+            .unwrap_or(AccountInfo {
+                client,
+                available: Decimal::ZERO,
+                held: Decimal::ZERO,
+                total: Decimal::ZERO,
+                locked: false,
+            });
+
+        // The first write error is returned once the processing is over.
+        if res.is_ok() {
+            res = writer.serialize(StatementRow {
+                row: row.to_string(),
+                ty: data.ty,
+                tx: data.id,
+                amount: data.amount,
+                outcome,
+                available: balance.available,
+                held: balance.held,
+                total: balance.total,
+                locked: balance.locked,
+            });
+        }
+    })?;
+
+    res?;
+    writer.flush()?;
+
+    Ok(EXIT_SUCCESS)
+}
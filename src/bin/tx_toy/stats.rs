@@ -0,0 +1,169 @@
+use crate::common;
+use crate::EXIT_SUCCESS;
+use clap::{ArgMatches, Command};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use tx_toy::{EngineError, TransactionOutcome, TransactionType};
+
+/// Aggregated statistics of a single transaction type.
+struct TypeStats {
+    /// Rows applied to the accounts, including the committed batch rows.
+    accepted: usize,
+    rejected: usize,
+
+    /// Rows applied within a batch, and then rolled back along with it.
+    rolled_back: usize,
+
+    /// Exact duplicates of already accepted rows, ignored by the engine.
+    duplicate: usize,
+
+    /// Control rows not applying to the account, e.g. accruals on locked accounts.
+    skipped: usize,
+
+    /// Total amount of the accepted transactions. For the transactions referencing other
+    /// transactions (disputes etc.), the amount of the referenced transaction is used. `None` if
+    /// the total is out of the decimal range.
+    volume: Option<Decimal>,
+}
+
+impl Default for TypeStats {
+    fn default() -> Self {
+        Self {
+            accepted: 0,
+            rejected: 0,
+            rolled_back: 0,
+            duplicate: 0,
+            skipped: 0,
+            volume: Some(Decimal::ZERO),
+        }
+    }
+}
+
+impl TypeStats {
+    fn accept(&mut self, volume: Decimal) {
+        self.accepted += 1;
+        self.volume = self.volume.and_then(|total| total.checked_add(volume));
+    }
+}
+
+pub fn command() -> Command<'static> {
+    Command::new("stats")
+        .about("Processes transactions and prints aggregate statistics")
+        .long_about(
+            "Processes transactions and prints aggregate statistics: the number of accepted, \
+             rejected, rolled back (by a failed batch), duplicate and skipped rows along with the \
+             accepted volume per transaction type, and the number of errors per error kind.",
+        )
+        .arg(common::input_arg())
+        .args(common::input_args())
+        .args(common::engine_args())
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let mut engine = common::create_engine(args)?;
+
+    let mut types = BTreeMap::<&'static str, TypeStats>::new();
+    let mut errors = BTreeMap::<&'static str, usize>::new();
+
+    // Type and volume of the rows applied within the open batch, accepted only once it commits.
+    let mut batch: Option<Vec<(&'static str, Decimal)>> = None;
+
+    common::process_inputs(&mut engine, args, |engine, row, res| {
+        if let Err(err) = &res {
+            *errors.entry(err.kind()).or_default() += 1;
+
+            if matches!(
+                err,
+                EngineError::BatchRolledBack { .. } | EngineError::BatchNotCommitted { .. }
+            ) {
+                for (ty, _) in batch.take().into_iter().flatten() {
+                    types.entry(ty).or_default().rolled_back += 1;
+                }
+            }
+        }
+
+        // Rows that couldn't be decoded are only counted as errors.
+        let data = match row.data {
+            Some(data) => data,
+            None => return,
+        };
+
+        let stats = types.entry(data.ty.name()).or_default();
+
+        let volume = match res {
+            Ok(TransactionOutcome::Applied | TransactionOutcome::Shortfall(_)) => {
+                // Credit limits and batch markers are not transfers, so they don't add to the
+                // volume.
+                match data.ty {
+                    TransactionType::CreditLimit
+                    | TransactionType::Begin
+                    | TransactionType::Commit => Decimal::ZERO,
+                    _ => engine
+                        .client_transaction(data.account_id, data.id)
+                        .map_or(Decimal::ZERO, |tx| tx.amount),
+                }
+            }
+
+            // Neither duplicates nor skipped rows change anything, so a rollback doesn't
+            // affect them.
+            Ok(TransactionOutcome::Duplicate) => {
+                stats.duplicate += 1;
+                return;
+            }
+
+            Ok(TransactionOutcome::Skipped) => {
+                stats.skipped += 1;
+                return;
+            }
+
+            Err(_) => {
+                stats.rejected += 1;
+                return;
+            }
+        };
+
+        if data.ty == TransactionType::Begin {
+            batch = Some(Vec::new());
+        }
+
+        match &mut batch {
+            Some(rows) => rows.push((data.ty.name(), volume)),
+            None => stats.accept(volume),
+        }
+
+        if data.ty == TransactionType::Commit {
+            for (ty, volume) in batch.take().into_iter().flatten() {
+                types.entry(ty).or_default().accept(volume);
+            }
+        }
+    })?;
+
+    println!(
+        "{:<16} {:>10} {:>10} {:>11} {:>10} {:>10} {:>20}",
+        "type", "accepted", "rejected", "rolled_back", "duplicate", "skipped", "volume"
+    );
+
+    for (ty, stats) in &types {
+        println!(
+            "{:<16} {:>10} {:>10} {:>11} {:>10} {:>10} {:>20}",
+            ty,
+            stats.accepted,
+            stats.rejected,
+            stats.rolled_back,
+            stats.duplicate,
+            stats.skipped,
+            stats
+                .volume
+                .map_or_else(|| "overflow".to_owned(), |volume| volume.to_string())
+        );
+    }
+
+    println!();
+    println!("{:<32} {:>10}", "error", "count");
+
+    for (kind, count) in &errors {
+        println!("{kind:<32} {count:>10}");
+    }
+
+    Ok(EXIT_SUCCESS)
+}
@@ -0,0 +1,53 @@
+use crate::common;
+use crate::{EXIT_INVALID_DATA, EXIT_SUCCESS};
+use clap::{ArgMatches, Command};
+use tx_toy::EngineError;
+
+pub fn command() -> Command<'static> {
+    Command::new("validate")
+        .about("Checks transactions and reports every invalid row")
+        .long_about(
+            "Checks all transactions without printing account balances, reporting every invalid \
+             row with its file name and line number.\n\n\
+             The rows are processed by a scratch engine, so that the references to the earlier \
+             transactions (disputes etc.) are validated as well. Exits with 3 if any of the rows \
+             is invalid.",
+        )
+        .arg(common::input_arg())
+        .args(common::input_args())
+        .args(common::engine_args())
+}
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<u8> {
+    let mut engine = common::create_engine(args)?;
+
+    let mut rows = 0;
+    let mut invalid = 0;
+
+    common::process_inputs(&mut engine, args, |_, row, res| {
+        // The batch left open is reported after the last row.
+        if !matches!(res, Err(EngineError::BatchNotCommitted { .. })) {
+            rows += 1;
+        }
+
+        if let Err(err) = res {
+            invalid += 1;
+
+            match (row.data, &err) {
+                (Some(data), _) => println!("{row}: {data:?}: {err}"),
+                (None, EngineError::DecodingError(_)) => {
+                    println!("{}: Error decoding transaction: {err}", row.source)
+                }
+                (None, _) => println!("{row}: {err}"),
+            }
+        }
+    })?;
+
+    println!("{rows} rows checked, {invalid} invalid");
+
+    Ok(if invalid > 0 {
+        EXIT_INVALID_DATA
+    } else {
+        EXIT_SUCCESS
+    })
+}
@@ -0,0 +1,106 @@
+use crate::account::CreditLimit;
+use crate::fees::FeeSchedule;
+use crate::holds::DepositHoldPolicy;
+use crate::interest::InterestPolicy;
+use crate::representment::RepresentmentPolicy;
+use crate::rules::Rule;
+use crate::status::DisputePolicy;
+use crate::transaction::IdScope;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+#[allow(clippy::enum_variant_names)]
+pub enum ConfigError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+
+    #[error(transparent)]
+    TomlError(#[from] toml::de::Error),
+
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Invalid `{section}` configuration: {reason}")]
+    InvalidConfigError {
+        section: &'static str,
+        reason: &'static str,
+    },
+}
+
+/// Engine configuration.
+///
+/// Every section is optional, and the default configuration matches the behaviour of an engine
+/// without any policies.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// Namespace of the transaction IDs.
+    pub id_scope: IdScope,
+
+    /// Risk rules evaluated before a transaction is applied.
+    pub rules: Vec<Rule>,
+
+    /// Automatic holds on large deposits.
+    pub deposit_hold: Option<DepositHoldPolicy>,
+
+    /// Initial credit limits of the client accounts.
+    pub credit_limits: Vec<CreditLimit>,
+
+    /// Transaction fees credited to the house account.
+    pub fees: Option<FeeSchedule>,
+
+    /// Interest accrued by the `accrue` control transactions.
+    pub interest: Option<InterestPolicy>,
+
+    /// Handling of the chargeback reversals.
+    pub representment: RepresentmentPolicy,
+
+    /// Dispute limits.
+    pub disputes: DisputePolicy,
+}
+
+impl EngineConfig {
+    /// Loads the configuration from a TOML or JSON file, and validates it. See [`load_file`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let config: Self = load_file(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the policies for the settings that parse, but make no sense, e.g. a hold that's
+    /// never released. Done by [`Self::from_file`], the configurations built otherwise have to be
+    /// validated explicitly.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for rule in &self.rules {
+            rule.validate()?;
+        }
+
+        if let Some(policy) = &self.deposit_hold {
+            policy.validate()?;
+        }
+
+        if let Some(policy) = &self.interest {
+            policy.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes a configuration file. The format is detected by the file extension: files ending
+/// with `.json` are parsed as JSON, everything else is parsed as TOML.
+pub fn load_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, ConfigError> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path)?;
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+        Ok(serde_json::from_str(&data)?)
+    } else {
+        Ok(toml::from_str(&data)?)
+    }
+}
@@ -0,0 +1,402 @@
+use crate::account::AccountInfo;
+use crate::id::{ClientId, TransactionId};
+use crate::snapshot::{Snapshot, TransactionInfo};
+use crate::transaction::TransactionStatus;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum DiffError {
+    #[error("Duplicate client: {0}")]
+    DuplicateClient(ClientId),
+
+    #[error("Balance change of client {0} out of the decimal range")]
+    DeltaOutOfRange(ClientId),
+}
+
+/// Change of an account balance, as the differences of the new and the old amounts.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct BalanceChange {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+}
+
+/// Change of an account lock state.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct LockChange {
+    pub client: ClientId,
+    pub from: bool,
+    pub to: bool,
+}
+
+/// Change of a transaction status. A missing status means the transaction is missing from the
+/// snapshot.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct TransactionChange {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub from: Option<TransactionStatus>,
+    pub to: Option<TransactionStatus>,
+}
+
+/// Differences between two snapshots, ordered by the client and transaction IDs as long as the
+/// snapshots are (see [`Snapshot::new`]).
+///
+/// Transaction statuses are only compared if both snapshots include the transactions.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct SnapshotDiff {
+    pub added_clients: Vec<AccountInfo>,
+    pub removed_clients: Vec<AccountInfo>,
+    pub balance_changes: Vec<BalanceChange>,
+    pub lock_changes: Vec<LockChange>,
+    pub transaction_changes: Vec<TransactionChange>,
+}
+
+impl SnapshotDiff {
+    /// Compares the snapshots. Fails if either of them has duplicate clients, or if a balance
+    /// changed by more than the decimal range allows, e.g. from a large negative to a large
+    /// positive amount.
+    pub fn new(old: &Snapshot, new: &Snapshot) -> Result<Self, DiffError> {
+        let mut diff = Self::default();
+
+        let mut old_accounts = BTreeMap::new();
+
+        for info in &old.accounts {
+            if old_accounts.insert(info.client, info).is_some() {
+                return Err(DiffError::DuplicateClient(info.client));
+            }
+        }
+
+        let mut new_clients = HashSet::new();
+
+        for new in &new.accounts {
+            if !new_clients.insert(new.client) {
+                return Err(DiffError::DuplicateClient(new.client));
+            }
+
+            let old = match old_accounts.remove(&new.client) {
+                Some(old) => old,
+                None => {
+                    diff.added_clients.push(new.clone());
+                    continue;
+                }
+            };
+
+            let delta = |to: Decimal, from: Decimal| {
+                to.checked_sub(from)
+                    .ok_or(DiffError::DeltaOutOfRange(new.client))
+            };
+
+            let change = BalanceChange {
+                client: new.client,
+                available: delta(new.available, old.available)?,
+                held: delta(new.held, old.held)?,
+                total: delta(new.total, old.total)?,
+            };
+
+            if !(change.available.is_zero() && change.held.is_zero() && change.total.is_zero()) {
+                diff.balance_changes.push(change);
+            }
+
+            if new.locked != old.locked {
+                diff.lock_changes.push(LockChange {
+                    client: new.client,
+                    from: old.locked,
+                    to: new.locked,
+                });
+            }
+        }
+
+        diff.removed_clients = old_accounts.into_values().cloned().collect();
+
+        if let (Some(old), Some(new)) = (&old.transactions, &new.transactions) {
+            diff.transaction_changes = transaction_changes(old, new);
+        }
+
+        Ok(diff)
+    }
+
+    /// Checks whether the snapshots are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.added_clients.is_empty()
+            && self.removed_clients.is_empty()
+            && self.balance_changes.is_empty()
+            && self.lock_changes.is_empty()
+            && self.transaction_changes.is_empty()
+    }
+}
+
+fn transaction_changes(old: &[TransactionInfo], new: &[TransactionInfo]) -> Vec<TransactionChange> {
+    let mut statuses = BTreeMap::<_, (Option<_>, Option<_>)>::new();
+
+    for info in old {
+        statuses.entry((info.client, info.tx)).or_default().0 = Some(info.status);
+    }
+
+    for info in new {
+        statuses.entry((info.client, info.tx)).or_default().1 = Some(info.status);
+    }
+
+    statuses
+        .into_iter()
+        .filter(|(_, (from, to))| from != to)
+        .map(|((client, tx), (from, to))| TransactionChange {
+            client,
+            tx,
+            from,
+            to,
+        })
+        .collect()
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for info in &self.added_clients {
+            writeln!(
+                f,
+                "+ Client {}: Available={} Held={} Total={} Locked={}",
+                info.client, info.available, info.held, info.total, info.locked
+            )?;
+        }
+
+        for info in &self.removed_clients {
+            writeln!(
+                f,
+                "- Client {}: Available={} Held={} Total={} Locked={}",
+                info.client, info.available, info.held, info.total, info.locked
+            )?;
+        }
+
+        for change in &self.balance_changes {
+            writeln!(
+                f,
+                "~ Client {}: Available{} Held{} Total{}",
+                change.client,
+                Delta(change.available),
+                Delta(change.held),
+                Delta(change.total)
+            )?;
+        }
+
+        for change in &self.lock_changes {
+            writeln!(
+                f,
+                "~ Client {}: Locked={} -> {}",
+                change.client, change.from, change.to
+            )?;
+        }
+
+        for change in &self.transaction_changes {
+            writeln!(
+                f,
+                "~ Transaction {} (client {}): {} -> {}",
+                change.tx,
+                change.client,
+                Status(change.from),
+                Status(change.to)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a balance difference with its sign.
+struct Delta(Decimal);
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_sign_negative() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "+{}", self.0)
+        }
+    }
+}
+
+/// Formats a transaction status, or its absence.
+struct Status(Option<TransactionStatus>);
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(status) => write!(f, "{status:?}"),
+            None => f.write_str("(missing)"),
+        }
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::{BalanceChange, DiffError, LockChange, SnapshotDiff, TransactionChange};
+    use crate::id::{ClientId, TransactionId};
+    use crate::snapshot::{Snapshot, TransactionInfo};
+    use crate::testing::account_info;
+    use crate::transaction::{StoredTransactionType, TransactionStatus};
+    use rust_decimal_macros::dec;
+
+    fn deposit(client: ClientId, tx: TransactionId, status: TransactionStatus) -> TransactionInfo {
+        TransactionInfo {
+            client,
+            tx,
+            ty: StoredTransactionType::Deposit,
+            amount: dec!(1.0),
+            status,
+        }
+    }
+
+    #[test]
+    fn account_dumps() {
+        let old = Snapshot::new(
+            vec![
+                account_info(1, "1.5", "0", false),
+                account_info(2, "2", "1", false),
+                account_info(3, "1", "0", false),
+            ],
+            None,
+        );
+
+        let new = Snapshot::new(
+            vec![
+                account_info(4, "1", "0", false),
+                account_info(2, "3", "0.5", true),
+                account_info(1, "1.50", "0", false),
+            ],
+            None,
+        );
+
+        let diff = SnapshotDiff::new(&old, &new).unwrap();
+
+        assert_eq!(
+            diff,
+            SnapshotDiff {
+                added_clients: vec![account_info(4, "1", "0", false)],
+                removed_clients: vec![account_info(3, "1", "0", false)],
+                balance_changes: vec![BalanceChange {
+                    client: 2,
+                    available: dec!(1),
+                    held: dec!(-0.5),
+                    total: dec!(0.5),
+                }],
+                lock_changes: vec![LockChange {
+                    client: 2,
+                    from: false,
+                    to: true,
+                }],
+                transaction_changes: vec![],
+            }
+        );
+
+        assert_eq!(
+            diff.to_string(),
+            "+ Client 4: Available=1 Held=0 Total=1 Locked=false\n\
+             - Client 3: Available=1 Held=0 Total=1 Locked=false\n\
+             ~ Client 2: Available+1 Held-0.5 Total+0.5\n\
+             ~ Client 2: Locked=false -> true\n"
+        );
+
+        assert!(SnapshotDiff::new(&old, &old).unwrap().is_empty());
+    }
+
+    #[test]
+    fn transaction_statuses() {
+        let old = Snapshot::new(
+            vec![],
+            Some(vec![
+                deposit(1, 1, TransactionStatus::Ok),
+                deposit(1, 2, TransactionStatus::UnderDispute),
+                deposit(2, 3, TransactionStatus::Ok),
+            ]),
+        );
+
+        let new = Snapshot::new(
+            vec![],
+            Some(vec![
+                deposit(1, 1, TransactionStatus::Ok),
+                deposit(1, 2, TransactionStatus::Cancelled),
+                deposit(2, 4, TransactionStatus::Ok),
+            ]),
+        );
+
+        let diff = SnapshotDiff::new(&old, &new).unwrap();
+
+        assert_eq!(
+            diff.transaction_changes,
+            vec![
+                TransactionChange {
+                    client: 1,
+                    tx: 2,
+                    from: Some(TransactionStatus::UnderDispute),
+                    to: Some(TransactionStatus::Cancelled),
+                },
+                TransactionChange {
+                    client: 2,
+                    tx: 3,
+                    from: Some(TransactionStatus::Ok),
+                    to: None,
+                },
+                TransactionChange {
+                    client: 2,
+                    tx: 4,
+                    from: None,
+                    to: Some(TransactionStatus::Ok),
+                },
+            ]
+        );
+
+        // Statuses are not compared against account dumps.
+        assert!(SnapshotDiff::new(&old, &Snapshot::new(vec![], None))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn diff_errors() {
+        let old = Snapshot::new(
+            vec![account_info(
+                1,
+                "-50000000000000000000000000000.5",
+                "0",
+                false,
+            )],
+            None,
+        );
+        let new = Snapshot::new(
+            vec![account_info(
+                1,
+                "50000000000000000000000000000.5",
+                "0",
+                false,
+            )],
+            None,
+        );
+
+        assert!(matches!(
+            SnapshotDiff::new(&old, &new),
+            Err(DiffError::DeltaOutOfRange(1))
+        ));
+
+        let duplicates = Snapshot::new(
+            vec![
+                account_info(1, "1", "0", false),
+                account_info(1, "2", "0", false),
+            ],
+            None,
+        );
+
+        assert!(matches!(
+            SnapshotDiff::new(&duplicates, &new),
+            Err(DiffError::DuplicateClient(1))
+        ));
+        assert!(matches!(
+            SnapshotDiff::new(&new, &duplicates),
+            Err(DiffError::DuplicateClient(1))
+        ));
+    }
+}
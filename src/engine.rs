@@ -1,12 +1,26 @@
-use crate::account::{AccountData, AccountStore, BalanceOperation, BalanceOperationError};
+use crate::account::{
+    AccountBalance, AccountData, AccountInfo, AccountStore, BalanceOperation, BalanceOperationError,
+};
+use crate::audit::{self, AuditError};
+use crate::batch::Batch;
+use crate::config::EngineConfig;
+use crate::fees::FeeSchedule;
+use crate::holds::DepositHolds;
+use crate::id::{ClientId, TransactionId};
 use crate::input::InputStreamError;
+use crate::interest::InterestPolicy;
+use crate::representment::{RepresentmentPolicy, UnlockPolicy};
+use crate::rules::RuleEngine;
+use crate::snapshot::{Snapshot, TransactionInfo};
+use crate::status::StatusMachine;
 use crate::transaction::{
-    RawTransactionData, StoredTransactionType, TransactionStatus, TransactionStore,
-    TransactionStoreError, TransactionType,
+    HoldStatus, RawTransactionData, StoredTransactionType, TransactionData, TransactionStatus,
+    TransactionStore, TransactionStoreError, TransactionType,
 };
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use thiserror::Error as ThisError;
+use tracing::{debug, error};
 
 #[derive(Debug, ThisError)]
 pub enum EngineError {
@@ -22,6 +36,9 @@ pub enum EngineError {
         actual: TransactionStatus,
     },
 
+    #[error("Dispute limit exceeded: Limit={limit}")]
+    DisputeLimitExceeded { limit: usize },
+
     #[error("Invalid transaction type: Required={required:?} Actual={actual:?}")]
     InvalidTransactionType {
         required: StoredTransactionType,
@@ -34,6 +51,19 @@ pub enum EngineError {
     #[error(transparent)]
     BalanceOperationError(#[from] BalanceOperationError),
 
+    #[error("Batch {batch} rolled back by row {row} of the batch: {source}")]
+    BatchRolledBack {
+        batch: TransactionId,
+        row: usize,
+        source: Box<EngineError>,
+    },
+
+    #[error("Batch {batch} has been rolled back by row {row} of the batch")]
+    BatchAborted { batch: TransactionId, row: usize },
+
+    #[error("Batch {batch} not committed, rolled back")]
+    BatchNotCommitted { batch: TransactionId },
+
     #[error("Internal error")]
     InternalError,
 }
@@ -49,41 +79,240 @@ pub enum ValidationError {
     #[error("Invalid transaction ID")]
     InvalidTransactionId,
 
+    #[error("Transaction ID conflicts with a different existing transaction")]
+    TransactionIdConflict,
+
     #[error("Invalid transaction amount")]
     InvalidAmount,
 
     #[error("Malformed transaction data")]
     MalformedTransactionData,
+
+    #[error("Rule violation: {rule}")]
+    RuleViolation { rule: &'static str },
+
+    #[error("Interest accrual is not configured")]
+    InterestNotConfigured,
+
+    #[error("Another batch is already open")]
+    BatchAlreadyOpen,
+
+    #[error("No open batch to commit")]
+    NoOpenBatch,
+}
+
+impl EngineError {
+    /// Short machine-readable name of the error kind, for aggregation purposes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EngineError::DecodingError(err) => err.kind(),
+            EngineError::InvalidTransactionData(err) => err.kind(),
+            EngineError::InvalidTransactionStatus { .. } => "invalid_transaction_status",
+            EngineError::DisputeLimitExceeded { .. } => "dispute_limit_exceeded",
+            EngineError::InvalidTransactionType { .. } => "invalid_transaction_type",
+            EngineError::TransactionStoreError(_) => "transaction_store_error",
+            EngineError::BalanceOperationError(err) => err.kind(),
+            EngineError::BatchRolledBack { .. } => "batch_rolled_back",
+            EngineError::BatchAborted { .. } => "batch_aborted",
+            EngineError::BatchNotCommitted { .. } => "batch_not_committed",
+            EngineError::InternalError => "internal_error",
+        }
+    }
+}
+
+impl ValidationError {
+    /// Short machine-readable name of the error kind, for aggregation purposes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ValidationError::AccountLocked => "account_locked",
+            ValidationError::InvalidAccountId => "invalid_account_id",
+            ValidationError::InvalidTransactionId => "invalid_transaction_id",
+            ValidationError::TransactionIdConflict => "transaction_id_conflict",
+            ValidationError::InvalidAmount => "invalid_amount",
+            ValidationError::MalformedTransactionData => "malformed_transaction_data",
+            ValidationError::RuleViolation { .. } => "rule_violation",
+            ValidationError::InterestNotConfigured => "interest_not_configured",
+            ValidationError::BatchAlreadyOpen => "batch_already_open",
+            ValidationError::NoOpenBatch => "no_open_batch",
+        }
+    }
 }
 
 pub type EngineResult<T> = Result<T, EngineError>;
 
+/// Describes what happened to a successfully processed transaction.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TransactionOutcome {
+    /// Transaction has been applied to the account.
+    Applied,
+
+    /// Transaction is an exact duplicate of an already accepted one and has been ignored.
+    Duplicate,
+
+    /// Chargeback has been applied, but the account didn't have enough funds to cover it. Contains
+    /// the uncovered amount, which is now owed by the client as a negative total balance.
+    Shortfall(Decimal),
+
+    /// Control transaction doesn't apply to the account and has been skipped, e.g. interest
+    /// accrual on a locked account.
+    Skipped,
+}
+
 /// Payment transaction engine.
 ///
 /// Current implementation holds both the accounts and transactions databases.
 /// Implements an interface to process transactions providing detailed error information in case
 /// transaction processing fails.
+#[derive(Clone)]
 pub struct Engine {
     accounts: AccountStore,
     transactions: TransactionStore,
+    rules: RuleEngine,
+    deposit_holds: DepositHolds,
+    fees: Option<FeeSchedule>,
+    interest: Option<InterestPolicy>,
+    representment: RepresentmentPolicy,
+    status: StatusMachine,
+
+    /// Sequence number of the transaction being processed. Incremented for every input row, and
+    /// used as a clock by the policies that operate on transaction windows.
+    sequence: u64,
+
+    /// Latest transaction timestamp seen, if the input provides timestamps.
+    clock: Option<u64>,
+
+    /// Batch opened by a `begin` row, if there's one.
+    batch: Option<Batch>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Engine {
+    /// Creates an engine without any policies configured.
     pub fn new() -> Self {
+        Self::with_config(Default::default())
+    }
+
+    /// Creates an engine with the policies defined by the configuration.
+    pub fn with_config(config: EngineConfig) -> Self {
         Self {
-            accounts: AccountStore::new(),
-            transactions: TransactionStore::new(),
+            accounts: AccountStore::with_credit_limits(config.credit_limits),
+            transactions: TransactionStore::new(config.id_scope),
+            rules: RuleEngine::new(config.rules),
+            deposit_holds: DepositHolds::new(config.deposit_hold),
+            fees: config.fees,
+            interest: config.interest,
+            representment: config.representment,
+            status: StatusMachine::new(config.disputes),
+            sequence: 0,
+            clock: None,
+            batch: None,
         }
     }
 
     /// Processes the raw transaction data as received from the data input streams. Provides
     /// detailed errors in case the transaction is invalid.
-    pub fn process_transaction(&mut self, data: &RawTransactionData) -> EngineResult<()> {
+    ///
+    /// Redelivery of an already accepted transaction is an idempotent no-op reported as
+    /// [`TransactionOutcome::Duplicate`], while reusing its ID with different data is rejected.
+    ///
+    /// The transactions between the `begin` and `commit` rows are applied atomically: if any of
+    /// them fails, all of the batch changes are rolled back, and the rest of the batch is rejected.
+    pub fn process_transaction(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
+        self.sequence += 1;
+        self.clock = self.clock.max(data.timestamp);
+
+        match data.ty {
+            TransactionType::Begin => return self.begin_batch(data),
+            TransactionType::Commit => return self.commit_batch(data),
+            _ => {}
+        }
+
+        let batch = match &mut self.batch {
+            Some(batch) => batch,
+            None => return self.release_and_apply(data),
+        };
+
+        batch.rows += 1;
+
+        if let Some(row) = batch.failed {
+            return Err(EngineError::BatchAborted {
+                batch: batch.id,
+                row,
+            });
+        }
+
+        let (id, row) = (batch.id, batch.rows);
+
+        match self.release_and_apply(data) {
+            Err(err) => {
+                self.rollback_batch();
+
+                Err(EngineError::BatchRolledBack {
+                    batch: id,
+                    row,
+                    source: Box::new(err),
+                })
+            }
+
+            res => res,
+        }
+    }
+
+    /// Finishes the processing of the input, rolling back the batch left open, if there's one.
+    pub fn finish(&mut self) -> EngineResult<()> {
+        let batch = match &self.batch {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+
+        let id = batch.id;
+
+        if batch.failed.is_none() {
+            self.rollback_batch();
+        }
+
+        self.batch = None;
+
+        Err(EngineError::BatchNotCommitted { batch: id })
+    }
+
+    /// Releases the deposit holds that are due, and applies the transaction. The holds are
+    /// released as a part of the transaction, so that they're rolled back along with the batch.
+    fn release_and_apply(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
+        self.release_deposit_holds();
+        self.apply_transaction(data)
+    }
+
+    /// Applies a single transaction.
+    fn apply_transaction(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
+        if self.is_duplicate(data) {
+            return Ok(TransactionOutcome::Duplicate);
+        }
+
+        // Locked accounts don't accrue interest, but that's not an error.
+        if data.ty == TransactionType::Accrue
+            && matches!(self.accounts.get(data.account_id), Some(account) if account.locked())
+        {
+            return Ok(TransactionOutcome::Skipped);
+        }
+
         // Perform general data validation for both the incoming transaction data and
         // the database state.
-        self.validate_transaction(&data)?;
+        self.validate_transaction(data)?;
 
-        match data.ty {
+        // Evaluate the risk rules before any balance operation is applied.
+        self.rules
+            .check(self.sequence, data, self.accounts.get(data.account_id))?;
+
+        let outcome = match data.ty {
             TransactionType::Deposit | TransactionType::Withdrawal => {
                 self.process_balance_operation(data)
             }
@@ -93,7 +322,44 @@ impl Engine {
             TransactionType::Resolve => self.process_resolution(data),
 
             TransactionType::Chargeback => self.process_chargeback(data),
-        }
+
+            TransactionType::CreditLimit => self.process_credit_limit(data),
+
+            TransactionType::Accrue => self.process_accrual(data),
+
+            TransactionType::Representment => self.process_representment(data),
+
+            TransactionType::Begin | TransactionType::Commit => Err(EngineError::InternalError),
+        }?;
+
+        self.rules.record(self.sequence, data);
+
+        Ok(outcome)
+    }
+
+    /// Returns the stored transaction data. Only deposits, withdrawals and interest postings are
+    /// stored.
+    ///
+    /// The IDs are only unique per client in the [`IdScope::Client`](crate::IdScope::Client) scope, so the transactions
+    /// can only be looked up with [`Self::client_transaction`] there, and this returns `None`.
+    pub fn transaction(&self, id: TransactionId) -> Option<&TransactionData> {
+        self.transactions.get_global(id)
+    }
+
+    /// Returns the stored transaction data of the client, in either of the ID scopes.
+    pub fn client_transaction(
+        &self,
+        client: ClientId,
+        id: TransactionId,
+    ) -> Option<&TransactionData> {
+        self.transactions.get(client, id)
+    }
+
+    /// Returns an iterator over all of the stored transactions.
+    ///
+    /// NOTE: The iterator is unordered.
+    pub fn transactions(&self) -> impl Iterator<Item = &TransactionData> {
+        self.transactions.iter()
     }
 
     /// Returns an iterator over all of client accounts.
@@ -103,7 +369,51 @@ impl Engine {
         self.accounts.iter()
     }
 
-    fn process_balance_operation(&mut self, data: &RawTransactionData) -> EngineResult<()> {
+    /// Returns the account data of the client, if the account exists.
+    pub fn account(&self, id: ClientId) -> Option<&AccountData> {
+        self.accounts.get(id)
+    }
+
+    /// Captures the account balances and the statuses of the stored transactions.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(
+            self.accounts.iter().map(AccountInfo::from).collect(),
+            Some(
+                self.transactions
+                    .iter()
+                    .map(TransactionInfo::from)
+                    .collect(),
+            ),
+        )
+    }
+
+    /// Checks the ledger invariants (balanced accounts, held funds matching the disputes and
+    /// deposit holds, balances matching the transactions), returning the first broken one.
+    ///
+    /// Walks all accounts and transactions, so it's meant for periodic self-checks rather than
+    /// every transaction.
+    pub fn audit(&self) -> Result<(), AuditError> {
+        audit::check_ledger(&self.accounts, &self.transactions)
+    }
+
+    /// Checks whether the transaction has already been accepted with exactly the same data.
+    fn is_duplicate(&self, data: &RawTransactionData) -> bool {
+        match data.ty {
+            TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Accrue => {
+                self.transactions
+                    .get(data.account_id, data.id)
+                    .is_some_and(|tx| tx.matches(data))
+            }
+
+            // Only stored transactions can be recognized as duplicates.
+            _ => false,
+        }
+    }
+
+    fn process_balance_operation(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
         // Safety guarantees at this point:
         //  - account either does not exist (created below) OR does exist and is upstanding;
         //  - transaction has a valid positive amount;
@@ -117,18 +427,159 @@ impl Engine {
             _ => return Err(EngineError::InternalError),
         };
 
-        // For simplicity, this call initializes an account if one does not already exist.
-        self.accounts
-            .get_mut(data.account_id)
-            .balance_mut()
-            .update(op)?;
+        let mut tx: TransactionData = data.try_into()?;
 
-        self.transactions.insert(data.try_into()?);
+        // Deposit fees are deducted from the deposited funds, and can't exceed them.
+        tx.fee = match data.ty {
+            TransactionType::Deposit => self.fee(data.ty, data.account_id, amount).min(amount),
+            _ => self.fee(data.ty, data.account_id, amount),
+        };
 
-        Ok(())
+        // For simplicity, this call initializes an account if one does not already exist. The
+        // operations are applied to a copy of the balance, so that it remains unchanged if any of
+        // them fail.
+        let mut balance = self.accounts.get_mut(data.account_id).balance().clone();
+
+        balance.update(op)?;
+        balance.update(BalanceOperation::WithdrawAvailable(tx.fee))?;
+
+        if data.ty == TransactionType::Deposit && self.deposit_holds.should_hold(amount) {
+            // Can't fail, since the funds have just been deposited.
+            balance.update(BalanceOperation::Hold(tx.net_amount()))?;
+
+            tx.hold = Some(HoldStatus::Active);
+
+            debug!(amount = %tx.net_amount(), "Deposit held");
+
+            self.deposit_holds
+                .place(tx.account_id, tx.id, self.sequence, self.clock);
+        }
+
+        let house_balance = credit_fee(&mut self.accounts, self.fees.as_ref(), tx.fee, None)?;
+
+        *self.accounts.get_mut(data.account_id).balance_mut() = balance;
+
+        if let Some((house, house_balance)) = house_balance {
+            *self.accounts.get_mut(house).balance_mut() = house_balance;
+        }
+
+        self.transactions.insert(tx);
+
+        Ok(TransactionOutcome::Applied)
+    }
+
+    fn begin_batch(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
+        if self.batch.is_some() {
+            return Err(ValidationError::BatchAlreadyOpen.into());
+        }
+
+        self.accounts.begin();
+        self.transactions.begin();
+        self.rules.begin();
+        self.deposit_holds.begin();
+
+        self.batch = Some(Batch {
+            id: data.id,
+            rows: 0,
+            failed: None,
+        });
+
+        debug!(batch = %data.id, "Batch opened");
+
+        Ok(TransactionOutcome::Applied)
+    }
+
+    fn commit_batch(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
+        match &self.batch {
+            None => return Err(ValidationError::NoOpenBatch.into()),
+            Some(batch) if batch.id != data.id => {
+                return Err(ValidationError::InvalidTransactionId.into())
+            }
+            _ => {}
+        }
+
+        let batch = self.batch.take().ok_or(EngineError::InternalError)?;
+
+        // The changes have already been rolled back.
+        if let Some(row) = batch.failed {
+            return Err(EngineError::BatchAborted {
+                batch: batch.id,
+                row,
+            });
+        }
+
+        self.accounts.commit();
+        self.transactions.commit();
+        self.rules.commit();
+        self.deposit_holds.commit();
+
+        debug!(batch = %batch.id, rows = batch.rows, "Batch committed");
+
+        Ok(TransactionOutcome::Applied)
+    }
+
+    /// Reverts all of the changes made by the open batch, and marks it as failed at its last row.
+    ///
+    /// The sequence number and the clock are left as they are: like the rejected rows, the rows of
+    /// the batch still count towards the transaction windows, and their timestamps have been seen.
+    fn rollback_batch(&mut self) {
+        self.accounts.rollback();
+        self.transactions.rollback();
+        self.rules.rollback();
+        self.deposit_holds.rollback();
+
+        if let Some(batch) = &mut self.batch {
+            batch.failed = Some(batch.rows);
+
+            debug!(batch = %batch.id, row = batch.rows, "Batch rolled back");
+        }
     }
 
-    fn process_dispute(&mut self, data: &RawTransactionData) -> EngineResult<()> {
+    /// Releases the automatic deposit holds that are due at the current sequence number and time.
+    ///
+    /// The releases aren't caused by the transaction being processed, so they don't fail it. A hold
+    /// that can't be released is logged and stays in place.
+    fn release_deposit_holds(&mut self) {
+        while let Some((client, id)) = self.deposit_holds.pop_due(self.sequence, self.clock) {
+            let tx = match self.transactions.get_mut(client, id) {
+                Some(tx) => tx,
+                None => {
+                    error!(client = %client, tx = %id, "Held deposit not found");
+                    continue;
+                }
+            };
+
+            // The hold may have already been cancelled by a chargeback.
+            if tx.hold != Some(HoldStatus::Active) {
+                continue;
+            }
+
+            // The disputed funds stay held until the dispute is settled.
+            if tx.status == TransactionStatus::UnderDispute {
+                tx.hold = Some(HoldStatus::Released);
+
+                debug!(client = %client, tx = %id, "Deposit hold taken over by the dispute");
+                continue;
+            }
+
+            let res = self
+                .accounts
+                .get_mut(tx.account_id)
+                .balance_mut()
+                .update(BalanceOperation::Release(tx.net_amount()));
+
+            if let Err(err) = res {
+                error!(client = %client, tx = %id, error = %err, "Deposit hold not released");
+                continue;
+            }
+
+            tx.hold = Some(HoldStatus::Released);
+
+            debug!(client = %client, tx = %id, "Deposit hold released");
+        }
+    }
+
+    fn process_dispute(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
         // Safety guarantees at this point:
         //  - account exists and is upstanding;
         //  - transaction ID is valid and transaction data exists in the database;
@@ -136,34 +587,41 @@ impl Engine {
 
         let tx = self
             .transactions
-            .get_mut(data.id)
+            .get_mut(data.account_id, data.id)
             .ok_or(EngineError::InternalError)?;
 
-        if tx.ty != StoredTransactionType::Deposit {
+        if !tx.ty.is_credit() {
             return Err(EngineError::InvalidTransactionType {
                 required: StoredTransactionType::Deposit,
                 actual: tx.ty,
             });
         }
 
-        if tx.status != TransactionStatus::Ok {
-            return Err(EngineError::InvalidTransactionStatus {
-                required: TransactionStatus::Ok,
-                actual: tx.status,
-            });
-        }
+        let status = self.status.transition(tx, data)?;
+
+        // The dispute holds the full transaction amount. If the deposit is under an automatic
+        // hold, the dispute only holds the fee on top of it, and the automatic hold stays active
+        // until it's due, so that resolving the dispute doesn't release it early.
+        let amount = if tx.hold == Some(HoldStatus::Active) {
+            tx.fee
+        } else {
+            tx.amount
+        };
 
         self.accounts
             .get_mut(data.account_id)
             .balance_mut()
-            .update(BalanceOperation::Hold(tx.amount))?;
+            .update(BalanceOperation::Hold(amount))?;
 
-        tx.status = TransactionStatus::UnderDispute;
+        tx.change_status(status, data);
 
-        Ok(())
+        Ok(TransactionOutcome::Applied)
     }
 
-    fn process_resolution(&mut self, data: &RawTransactionData) -> EngineResult<()> {
+    fn process_resolution(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
         // Safety guarantees at this point:
         //  - account exists and is upstanding;
         //  - transaction ID is valid and transaction data exists in the database;
@@ -171,27 +629,32 @@ impl Engine {
 
         let tx = self
             .transactions
-            .get_mut(data.id)
+            .get_mut(data.account_id, data.id)
             .ok_or(EngineError::InternalError)?;
 
-        if tx.status != TransactionStatus::UnderDispute {
-            return Err(EngineError::InvalidTransactionStatus {
-                required: TransactionStatus::UnderDispute,
-                actual: tx.status,
-            });
-        }
+        let status = self.status.transition(tx, data)?;
+
+        // The automatic hold that isn't due yet keeps holding the deposited funds.
+        let amount = if tx.hold == Some(HoldStatus::Active) {
+            tx.fee
+        } else {
+            tx.amount
+        };
 
         self.accounts
             .get_mut(data.account_id)
             .balance_mut()
-            .update(BalanceOperation::Release(tx.amount))?;
+            .update(BalanceOperation::Release(amount))?;
 
-        tx.status = TransactionStatus::Ok;
+        tx.change_status(status, data);
 
-        Ok(())
+        Ok(TransactionOutcome::Applied)
     }
 
-    fn process_chargeback(&mut self, data: &RawTransactionData) -> EngineResult<()> {
+    fn process_chargeback(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
         // Safety guarantees at this point:
         //  - account exists and is upstanding;
         //  - transaction ID is valid and transaction data exists in the database;
@@ -199,43 +662,202 @@ impl Engine {
 
         let tx = self
             .transactions
-            .get_mut(data.id)
+            .get(data.account_id, data.id)
             .ok_or(EngineError::InternalError)?;
 
-        if tx.status != TransactionStatus::UnderDispute {
-            return Err(EngineError::InvalidTransactionStatus {
-                required: TransactionStatus::UnderDispute,
-                actual: tx.status,
-            });
+        let status = self.status.transition(tx, data)?;
+
+        let chargeback_fee = self.fee(data.ty, data.account_id, tx.amount);
+        let house = self.fees.as_ref().map(|fees| fees.house_account);
+
+        let tx = self
+            .transactions
+            .get_mut(data.account_id, data.id)
+            .ok_or(EngineError::InternalError)?;
+
+        // The operations are applied to copies of the balances, so that they remain unchanged if
+        // any of them fail.
+        let mut balance = self.accounts.get_mut(data.account_id).balance().clone();
+
+        balance.update(BalanceOperation::WithdrawHeld(tx.amount))?;
+
+        // The fee of the charged back transaction is refunded by the house account, even if it
+        // takes the house balance negative.
+        let refund = match house.filter(|_| tx.fee > Decimal::ZERO) {
+            Some(house) => {
+                let mut house_balance = self.accounts.get_mut(house).balance().clone();
+
+                house_balance.update(BalanceOperation::Debit(tx.fee))?;
+                balance.update(BalanceOperation::Deposit(tx.fee))?;
+
+                Some((house, house_balance))
+            }
+
+            None => None,
+        };
+
+        // Charge the chargeback fee only as far as the available funds cover it.
+        tx.chargeback_fee = chargeback_fee.min(balance.available().max(Decimal::ZERO));
+        balance.update(BalanceOperation::WithdrawAvailable(tx.chargeback_fee))?;
+
+        let house_balance = credit_fee(
+            &mut self.accounts,
+            self.fees.as_ref(),
+            tx.chargeback_fee,
+            refund,
+        )?;
+
+        if let Some((house, house_balance)) = house_balance {
+            *self.accounts.get_mut(house).balance_mut() = house_balance;
+        }
+
+        // The held funds are gone along with the automatic hold, if it wasn't due yet.
+        if tx.hold == Some(HoldStatus::Active) {
+            tx.hold = Some(HoldStatus::Released);
+        }
+
+        let account = self.accounts.get_mut(data.account_id);
+
+        *account.balance_mut() = balance;
+        account.set_locked(true);
+        account.set_chargebacks(account.chargebacks() + 1);
+
+        debug!(client = %data.account_id, "Account locked");
+
+        tx.change_status(status, data);
+
+        // The disputed funds may have been held on credit, in which case the chargeback drives
+        // the total balance negative.
+        let total = account.balance().total();
+        let shortfall = tx.amount.min(-total);
+
+        if shortfall > Decimal::ZERO {
+            Ok(TransactionOutcome::Shortfall(shortfall))
+        } else {
+            Ok(TransactionOutcome::Applied)
+        }
+    }
+
+    fn process_representment(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
+        // Safety guarantees at this point:
+        //  - account exists, but may be locked;
+        //  - transaction ID is valid and transaction data exists in the database;
+        //  - account ID matches original transaction's account ID;
+
+        let tx = self
+            .transactions
+            .get_mut(data.account_id, data.id)
+            .ok_or(EngineError::InternalError)?;
+
+        let status = self.status.transition(tx, data)?;
+
+        // Re-credit the charged back amount, and charge the fee refunded by the chargeback once
+        // again. The operations are applied to copies of the balances, so that they remain
+        // unchanged if any of them fail.
+        let mut balance = self.accounts.get_mut(data.account_id).balance().clone();
+
+        balance.update(BalanceOperation::Deposit(tx.amount))?;
+        balance.update(BalanceOperation::WithdrawAvailable(tx.fee))?;
+
+        let house_balance = credit_fee(&mut self.accounts, self.fees.as_ref(), tx.fee, None)?;
+
+        *self.accounts.get_mut(data.account_id).balance_mut() = balance;
+
+        if let Some((house, house_balance)) = house_balance {
+            *self.accounts.get_mut(house).balance_mut() = house_balance;
         }
 
+        tx.change_status(status, data);
+
         let account = self.accounts.get_mut(data.account_id);
 
-        account
+        account.set_chargebacks(account.chargebacks() - 1);
+
+        let unlock = match self.representment.unlock {
+            UnlockPolicy::Never => false,
+            UnlockPolicy::Always => true,
+            UnlockPolicy::IfNoOtherChargebacks => account.chargebacks() == 0,
+        };
+
+        if unlock {
+            account.set_locked(false);
+
+            debug!(client = %data.account_id, "Account unlocked");
+        }
+
+        Ok(TransactionOutcome::Applied)
+    }
+
+    fn process_credit_limit(
+        &mut self,
+        data: &RawTransactionData,
+    ) -> EngineResult<TransactionOutcome> {
+        // Safety guarantees at this point:
+        //  - account either does not exist (created below) OR does exist and is upstanding;
+        //  - transaction has a valid non-negative amount;
+
+        let limit = data.amount.ok_or(EngineError::InternalError)?;
+
+        self.accounts
+            .get_mut(data.account_id)
             .balance_mut()
-            .update(BalanceOperation::WithdrawHeld(tx.amount))?;
+            .update(BalanceOperation::SetCreditLimit(limit))?;
 
-        account.set_locked(true);
+        Ok(TransactionOutcome::Applied)
+    }
+
+    fn process_accrual(&mut self, data: &RawTransactionData) -> EngineResult<TransactionOutcome> {
+        // Safety guarantees at this point:
+        //  - account exists and is upstanding;
+        //  - interest policy is configured;
+        //  - transaction has a valid positive number of days;
+        //  - transaction ID is unique and can be inserted into the database;
 
-        tx.status = TransactionStatus::Cancelled;
+        let days = data.amount.ok_or(EngineError::InternalError)?;
+        let policy = self.interest.as_ref().ok_or(EngineError::InternalError)?;
+        let balance = self.accounts.get_mut(data.account_id).balance_mut();
 
-        Ok(())
+        let mut tx: TransactionData = data.try_into()?;
+
+        tx.amount = policy.calculate(balance, days)?;
+
+        // The interest is posted as a deposit-like transaction, so that it can be reversed.
+        balance.update(BalanceOperation::Deposit(tx.amount))?;
+
+        self.transactions.insert(tx);
+
+        Ok(TransactionOutcome::Applied)
+    }
+
+    /// Calculates the fee for the transaction according to the fee schedule, if there's one.
+    fn fee(&self, ty: TransactionType, account_id: ClientId, amount: Decimal) -> Decimal {
+        self.fees
+            .as_ref()
+            .map_or(Decimal::ZERO, |fees| fees.calculate(ty, account_id, amount))
     }
 
     /// Performs common validations shared between multiple transaction types.
     fn validate_transaction(&self, data: &RawTransactionData) -> Result<(), ValidationError> {
         let account = self.accounts.get(data.account_id);
 
-        // If the account does exist and it's locked, it's a no-go. If the account does not exist,
-        // it'll be created later.
-        if matches!(account, Some(account) if account.locked()) {
+        // If the account does exist and it's locked, it's a no-go, unless it's a representment
+        // reversing the chargeback that locked it. If the account does not exist, it'll be
+        // created later.
+        if data.ty != TransactionType::Representment
+            && matches!(account, Some(account) if account.locked())
+        {
             return Err(ValidationError::AccountLocked);
         }
 
         match data.ty {
             TransactionType::Deposit | TransactionType::Withdrawal => {
-                if self.transactions.exists(data.id) {
-                    return Err(ValidationError::InvalidTransactionId);
+                if self.transactions.exists(data.account_id, data.id) {
+                    // Exact duplicates are filtered out before validation, so this is an ID
+                    // reused for a different transaction.
+                    return Err(ValidationError::TransactionIdConflict);
                 }
 
                 static ZERO: Decimal = dec!(0.0);
@@ -251,12 +873,15 @@ impl Engine {
                 }
             }
 
-            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback
+            | TransactionType::Representment => {
                 if account.is_none() {
                     return Err(ValidationError::InvalidAccountId);
                 }
 
-                if let Some(tx) = self.transactions.get(data.id) {
+                if let Some(tx) = self.transactions.get(data.account_id, data.id) {
                     if tx.account_id != data.account_id {
                         return Err(ValidationError::InvalidAccountId);
                     }
@@ -264,35 +889,85 @@ impl Engine {
                     return Err(ValidationError::InvalidTransactionId);
                 }
 
-                if !self.transactions.exists(data.id) {
+                if !self.transactions.exists(data.account_id, data.id) {
                     return Err(ValidationError::InvalidTransactionId);
                 }
 
-                if let Some(_) = &data.amount {
+                if data.amount.is_some() {
                     // We do not expect to have any amount for these types of transactions.
                     return Err(ValidationError::MalformedTransactionData);
                 }
 
                 Ok(())
             }
+
+            TransactionType::Accrue => {
+                if self.interest.is_none() {
+                    return Err(ValidationError::InterestNotConfigured);
+                }
+
+                if account.is_none() {
+                    return Err(ValidationError::InvalidAccountId);
+                }
+
+                if self.transactions.exists(data.account_id, data.id) {
+                    return Err(ValidationError::TransactionIdConflict);
+                }
+
+                match data.amount {
+                    Some(days) if days > Decimal::ZERO => Ok(()),
+                    Some(_) => Err(ValidationError::InvalidAmount),
+                    None => Err(ValidationError::MalformedTransactionData),
+                }
+            }
+
+            TransactionType::CreditLimit => match data.amount {
+                Some(limit) if limit >= Decimal::ZERO => Ok(()),
+                Some(_) => Err(ValidationError::InvalidAmount),
+                None => Err(ValidationError::MalformedTransactionData),
+            },
+
+            // Batch markers are handled before validation.
+            TransactionType::Begin | TransactionType::Commit => Ok(()),
         }
     }
 }
 
-#[cfg(test)]
+/// Credits the fee charged from a client to a copy of the house account balance (or to the given
+/// one), returning it to be written back along with the client's balance. A free function, so
+/// that it can be called while a stored transaction is borrowed.
+fn credit_fee(
+    accounts: &mut AccountStore,
+    fees: Option<&FeeSchedule>,
+    fee: Decimal,
+    house_balance: Option<(ClientId, AccountBalance)>,
+) -> EngineResult<Option<(ClientId, AccountBalance)>> {
+    let house = match fees.filter(|_| fee > Decimal::ZERO) {
+        Some(fees) => fees.house_account,
+        None => return Ok(house_balance),
+    };
+
+    let mut balance = match house_balance {
+        Some((_, balance)) => balance,
+        None => accounts.get_mut(house).balance().clone(),
+    };
+
+    balance.update(BalanceOperation::Deposit(fee))?;
+
+    Ok(Some((house, balance)))
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
 mod test {
     use super::Engine;
     use crate::account::{AccountBalance, BalanceOperationError};
-    use crate::engine::{EngineError, ValidationError};
-    use crate::input::InputStream;
-    use crate::transaction::RawTransactionData;
+    use crate::config::EngineConfig;
+    use crate::engine::{EngineError, TransactionOutcome, ValidationError};
+    use crate::testing::create_input;
+    use crate::transaction::{IdScope, TransactionStatus};
     use rust_decimal_macros::dec;
 
-    fn create_input(csv_data: &'static str) -> Vec<RawTransactionData> {
-        let test_input = InputStream::from_reader(&csv_data.as_bytes()[..]).unwrap();
-        test_input.map(|tx| tx.unwrap()).collect::<Vec<_>>()
-    }
-
     #[test]
     fn transaction_types() -> anyhow::Result<()> {
         let test_input = create_input(
@@ -441,4 +1116,180 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn duplicate_transactions() -> anyhow::Result<()> {
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 15.0
+            deposit, 1, 1, 15.0
+            withdrawal, 1, 2, 5.0
+            withdrawal, 1, 2, 5.00
+            deposit, 2, 1, 15.0
+            deposit, 1, 1, 10.0
+            withdrawal, 1, 1, 15.0",
+        );
+
+        let mut input = input.iter();
+        let mut engine = Engine::new();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        assert_eq!(next()?, TransactionOutcome::Applied);
+        assert_eq!(next()?, TransactionOutcome::Duplicate);
+        assert_eq!(next()?, TransactionOutcome::Applied);
+        assert_eq!(next()?, TransactionOutcome::Duplicate);
+
+        for _ in 0..3 {
+            assert!(matches!(
+                next(),
+                Err(EngineError::InvalidTransactionData(
+                    ValidationError::TransactionIdConflict
+                ))
+            ));
+        }
+
+        assert_eq!(
+            engine.accounts.balance(1).unwrap(),
+            &AccountBalance::with_amount(dec!(10.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn credit_limits() -> anyhow::Result<()> {
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            withdrawal, 1, 2, 15.0
+            credit_limit, 1, 3, 10.0
+            withdrawal, 1, 4, 15.0
+            dispute, 1, 1
+            deposit, 1, 5, 7.0
+            dispute, 1, 1
+            chargeback, 1, 1",
+        );
+
+        let mut input = input.iter();
+        let mut engine = Engine::new();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+
+        assert!(matches!(
+            next(),
+            Err(EngineError::BalanceOperationError(
+                BalanceOperationError::InsufficientAvailableFunds { .. }
+            ))
+        ));
+
+        next()?;
+        next()?;
+
+        // Holding the disputed funds would exceed the credit limit.
+        assert!(matches!(
+            next(),
+            Err(EngineError::BalanceOperationError(
+                BalanceOperationError::InsufficientAvailableFunds { .. }
+            ))
+        ));
+
+        next()?;
+        next()?;
+
+        assert_eq!(next()?, TransactionOutcome::Shortfall(dec!(8.0)));
+
+        let account = engine.accounts.get(1).unwrap();
+
+        assert!(account.locked());
+        assert_eq!(account.balance().available(), dec!(-8.0));
+        assert_eq!(account.balance().total(), dec!(-8.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn client_id_scope() -> anyhow::Result<()> {
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            deposit, 2, 1, 20.0
+            deposit, 1, 1, 10.0
+            dispute, 2, 1
+            dispute, 3, 1",
+        );
+
+        let mut input = input.iter();
+
+        let mut engine = Engine::with_config(EngineConfig {
+            id_scope: IdScope::Client,
+            ..Default::default()
+        });
+
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        assert_eq!(next()?, TransactionOutcome::Applied);
+        assert_eq!(next()?, TransactionOutcome::Applied);
+        assert_eq!(next()?, TransactionOutcome::Duplicate);
+        assert_eq!(next()?, TransactionOutcome::Applied);
+
+        // The account doesn't exist.
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::InvalidAccountId
+            ))
+        ));
+
+        assert_eq!(
+            engine.client_transaction(1, 1).unwrap().status,
+            TransactionStatus::Ok
+        );
+        assert_eq!(
+            engine.client_transaction(2, 1).unwrap().status,
+            TransactionStatus::UnderDispute
+        );
+
+        // The ID alone doesn't identify a transaction.
+        assert!(engine.transaction(1).is_none());
+        assert_eq!(
+            engine.accounts.balance(2).unwrap(),
+            &AccountBalance::with_amount(dec!(20.0), dec!(20.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn amount_overflow() -> anyhow::Result<()> {
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 50000000000000000000000000000.0
+            deposit, 1, 2, 50000000000000000000000000000.0
+            deposit, 1, 3, 0.00000000000000000000000001
+            withdrawal, 1, 4, 1.0",
+        );
+
+        let mut engine = Engine::new();
+
+        engine.process_transaction(&input[0])?;
+
+        // Neither overflowing the balance nor rounding it off is allowed.
+        for tx in &input[1..3] {
+            let err = engine.process_transaction(tx).unwrap_err();
+            assert_eq!(err.kind(), "amount_overflow");
+        }
+
+        engine.process_transaction(&input[3])?;
+
+        assert_eq!(
+            engine.accounts.balance(1).unwrap(),
+            &AccountBalance::with_amount(dec!(49999999999999999999999999999), dec!(0.0))?
+        );
+
+        assert!(engine.audit().is_ok());
+        assert!(engine.client_transaction(1, 2).is_none());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,269 @@
+use crate::id::ClientId;
+use crate::transaction::TransactionType;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+/// Number of decimal places fees are rounded to.
+const FEE_DECIMAL_PLACES: u32 = 4;
+
+/// Fee charged for a single transaction.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fee {
+    /// Fixed amount regardless of the transaction amount.
+    Flat { amount: Decimal },
+
+    /// Percentage of the transaction amount.
+    Percentage { percent: Decimal },
+
+    /// Fee picked by the transaction amount. Tiers are matched in order, and the first tier with
+    /// `up_to` not less than the amount (or without `up_to`) applies. No fee is charged if none of
+    /// the tiers match.
+    Tiered { tiers: Vec<FeeTier> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeeTier {
+    pub up_to: Option<Decimal>,
+
+    #[serde(flatten)]
+    pub fee: Fee,
+}
+
+impl Fee {
+    /// Calculates the fee for the transaction amount.
+    pub fn calculate(&self, amount: Decimal) -> Decimal {
+        let fee = match self {
+            Fee::Flat { amount } => *amount,
+
+            // Only the amounts close to the decimal range overflow the product, in which case the
+            // percentage is taken of the scaled down amount.
+            Fee::Percentage { percent } => amount
+                .checked_mul(*percent)
+                .map(|fee| fee / Decimal::ONE_HUNDRED)
+                .unwrap_or_else(|| (amount / Decimal::ONE_HUNDRED).saturating_mul(*percent)),
+
+            Fee::Tiered { tiers } => tiers
+                .iter()
+                .find(|tier| tier.up_to.is_none_or(|up_to| amount <= up_to))
+                .map_or(Decimal::ZERO, |tier| tier.fee.calculate(amount)),
+        };
+
+        fee.round_dp(FEE_DECIMAL_PLACES).max(Decimal::ZERO)
+    }
+}
+
+/// Fee schedule, as specified in the configuration.
+///
+/// Collected fees are credited to the house account, which is never charged any fees itself.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeSchedule {
+    pub house_account: ClientId,
+    pub deposit: Option<Fee>,
+    pub withdrawal: Option<Fee>,
+    pub chargeback: Option<Fee>,
+}
+
+impl FeeSchedule {
+    /// Calculates the fee the client is charged for a transaction of the type and the amount.
+    pub fn calculate(&self, ty: TransactionType, account_id: ClientId, amount: Decimal) -> Decimal {
+        if account_id == self.house_account {
+            return Decimal::ZERO;
+        }
+
+        let fee = match ty {
+            TransactionType::Deposit => &self.deposit,
+            TransactionType::Withdrawal => &self.withdrawal,
+            TransactionType::Chargeback => &self.chargeback,
+            _ => &None,
+        };
+
+        fee.as_ref()
+            .map_or(Decimal::ZERO, |fee| fee.calculate(amount))
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::Fee;
+    use crate::account::AccountBalance;
+    use crate::engine::EngineError;
+    use crate::testing::{account_balance, create_engine, create_input};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn fee_types() -> anyhow::Result<()> {
+        let fee: Fee = toml::from_str(
+            r#"
+            type = "tiered"
+
+            [[tiers]]
+            up_to = "100.0"
+            type = "flat"
+            amount = "1.0"
+
+            [[tiers]]
+            type = "percentage"
+            percent = "0.5"
+            "#,
+        )?;
+
+        assert_eq!(fee.calculate(dec!(50.0)), dec!(1.0));
+        assert_eq!(fee.calculate(dec!(100.0)), dec!(1.0));
+        assert_eq!(fee.calculate(dec!(1000.0)), dec!(5.0));
+        assert_eq!(fee.calculate(dec!(0.12345)), dec!(1.0));
+        assert_eq!(
+            Fee::Percentage { percent: dec!(1) }.calculate(dec!(0.12345)),
+            dec!(0.0012)
+        );
+
+        // The product overflowing the decimal range doesn't panic.
+        assert_eq!(
+            Fee::Percentage { percent: dec!(50) }.calculate(Decimal::MAX),
+            (Decimal::MAX / Decimal::ONE_HUNDRED * dec!(50)).round_dp(4)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fee_charging() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [fees]
+            house_account = 0
+
+            [fees.deposit]
+            type = "flat"
+            amount = "1.0"
+
+            [fees.withdrawal]
+            type = "percentage"
+            percent = "10"
+
+            [fees.chargeback]
+            type = "flat"
+            amount = "15.0"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 20.0
+            withdrawal, 1, 2, 18.0
+            withdrawal, 1, 3, 10.0
+            deposit, 1, 4, 10.0
+            dispute, 1, 4
+            chargeback, 1, 4",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+
+        // Not enough funds to cover the fee.
+        assert!(matches!(
+            next(),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        next()?;
+        next()?;
+        next()?;
+        next()?;
+
+        // 20 - 1 - 10 - 1 + 10 - 1 - 10 + 1 - 8 (capped chargeback fee).
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(0.0), dec!(0.0))?
+        );
+
+        // 1 + 1 + 1 - 1 + 8.
+        assert_eq!(
+            account_balance(&engine, 0),
+            AccountBalance::with_amount(dec!(10.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fee_refund_shortfall() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [fees]
+            house_account = 0
+
+            [fees.deposit]
+            type = "flat"
+            amount = "1.0"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            deposit, 1, 2, 5.0
+            withdrawal, 0, 3, 2.0
+            dispute, 1, 1
+            chargeback, 1, 1",
+        );
+
+        for tx in &input {
+            engine.process_transaction(tx)?;
+        }
+
+        // The refund isn't covered by the withdrawn fees, but doesn't block the chargeback.
+        assert_eq!(account_balance(&engine, 0).total(), dec!(-1.0));
+
+        // 10 - 1 + 5 - 1 - 10 + 1.
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(4.0), dec!(0.0))?
+        );
+
+        assert!(engine.audit().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fee_credit_overflow() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [fees]
+            house_account = 0
+
+            [fees.deposit]
+            type = "flat"
+            amount = "0.5"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 0, 1, 50000000000000000000000000000.0
+            deposit, 1, 2, 10.0",
+        );
+
+        engine.process_transaction(&input[0])?;
+
+        // The fee would have to be rounded off the house account balance, so the deposit is rejected as a whole.
+        assert!(matches!(
+            engine.process_transaction(&input[1]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        assert_eq!(
+            account_balance(&engine, 0).total(),
+            dec!(50000000000000000000000000000)
+        );
+        assert_eq!(account_balance(&engine, 1).total(), dec!(0));
+
+        Ok(())
+    }
+}
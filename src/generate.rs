@@ -0,0 +1,463 @@
+use crate::id::{self, ClientId, TransactionId};
+use crate::transaction::{RawTransactionData, TransactionType};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use thiserror::Error as ThisError;
+
+/// Number of decimal places of the generated amounts.
+const AMOUNT_DECIMAL_PLACES: u32 = 4;
+
+/// Largest generated deposit, in the units of the last decimal place (i.e. `1000.0000`).
+const MAX_DEPOSIT_UNITS: i64 = 1000 * 10_i64.pow(AMOUNT_DECIMAL_PLACES);
+
+/// Generated transaction types, in the order of the [`TypeMix`] weights.
+const TYPES: [TransactionType; 5] = [
+    TransactionType::Deposit,
+    TransactionType::Withdrawal,
+    TransactionType::Dispute,
+    TransactionType::Resolve,
+    TransactionType::Chargeback,
+];
+
+#[derive(Debug, ThisError)]
+pub enum GeneratorError {
+    #[error("Invalid transaction type mix: {0}")]
+    InvalidMix(String),
+
+    #[error("Invalid fraction of invalid rows: {0}")]
+    InvalidFraction(f64),
+
+    #[error("Number of clients must be positive")]
+    NoClients,
+
+    #[error("Too many {0} for the ID type")]
+    IdOutOfRange(&'static str),
+}
+
+/// Relative weights of the generated transaction types.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TypeMix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+    pub resolve: u32,
+    pub chargeback: u32,
+}
+
+impl Default for TypeMix {
+    fn default() -> Self {
+        Self {
+            deposit: 60,
+            withdrawal: 30,
+            dispute: 5,
+            resolve: 4,
+            chargeback: 1,
+        }
+    }
+}
+
+impl TypeMix {
+    fn weights(&self) -> [u32; 5] {
+        [
+            self.deposit,
+            self.withdrawal,
+            self.dispute,
+            self.resolve,
+            self.chargeback,
+        ]
+    }
+}
+
+/// Parses a comma-separated list of `type=weight` pairs, e.g. `deposit=9,withdrawal=1`. The
+/// types that aren't listed aren't generated.
+impl FromStr for TypeMix {
+    type Err = GeneratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = TypeMix {
+            deposit: 0,
+            withdrawal: 0,
+            dispute: 0,
+            resolve: 0,
+            chargeback: 0,
+        };
+
+        for pair in s.split(',') {
+            let (ty, weight) = pair.split_once('=').ok_or_else(|| {
+                GeneratorError::InvalidMix(format!("Expected type=weight: {pair}"))
+            })?;
+
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| GeneratorError::InvalidMix(format!("Invalid weight: {pair}")))?;
+
+            match ty.trim() {
+                "deposit" => mix.deposit = weight,
+                "withdrawal" => mix.withdrawal = weight,
+                "dispute" => mix.dispute = weight,
+                "resolve" => mix.resolve = weight,
+                "chargeback" => mix.chargeback = weight,
+                ty => return Err(GeneratorError::InvalidMix(format!("Unknown type: {ty}"))),
+            }
+        }
+
+        Ok(mix)
+    }
+}
+
+/// Synthetic workload parameters.
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    /// Number of rows to generate.
+    pub rows: u64,
+
+    /// Number of clients, with the IDs from 1 up to it.
+    pub clients: u64,
+
+    pub mix: TypeMix,
+
+    /// Fraction of the rows to be rejected by the engine, from 0 to 1.
+    pub invalid_fraction: f64,
+
+    /// Seed of the random number generator. The same parameters and seed generate the same rows.
+    pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            rows: 1000,
+            clients: 10,
+            mix: TypeMix::default(),
+            invalid_fraction: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Deposit of a client, as tracked by the generator.
+#[derive(Debug, Clone, Copy)]
+struct Deposit {
+    tx: u64,
+    units: i64,
+}
+
+/// Client account state, as tracked by the generator to keep the valid rows valid.
+#[derive(Debug, Default)]
+struct ClientState {
+    available: i64,
+    deposits: Vec<Deposit>,
+    disputed: Vec<Deposit>,
+}
+
+/// Ways of generating a row rejected by the engine.
+#[derive(Debug, Clone, Copy)]
+enum Invalid {
+    InsufficientFunds,
+    UnknownTransaction,
+    NotDisputed,
+    NonPositiveAmount,
+    IdConflict,
+}
+
+const INVALID: [Invalid; 5] = [
+    Invalid::InsufficientFunds,
+    Invalid::UnknownTransaction,
+    Invalid::NotDisputed,
+    Invalid::NonPositiveAmount,
+    Invalid::IdConflict,
+];
+
+/// Synthetic workload generator, yielding transaction rows with the given mix of types.
+///
+/// The rows are valid for an engine with the default configuration, except for the requested
+/// fraction of invalid ones: withdrawals exceeding the available funds, disputes of unknown
+/// transactions, resolutions of undisputed deposits, deposits with non-positive amounts, and
+/// deposits reusing the IDs of other ones. Disputes, resolutions and chargebacks reference the
+/// client's earlier deposits, and rows that can't be generated validly (e.g. a withdrawal from an
+/// empty account) are replaced with deposits. Chargebacks lock the clients, except for the last
+/// unlocked one, so the rows concentrate on fewer clients over time.
+pub struct Generator {
+    config: WorkloadConfig,
+    rng: ChaCha8Rng,
+    types: WeightedIndex<u32>,
+    clients: Vec<ClientState>,
+
+    /// Indices of the clients that aren't locked.
+    active: Vec<usize>,
+
+    generated: u64,
+    next_tx: u64,
+}
+
+impl Generator {
+    pub fn new(config: WorkloadConfig) -> Result<Self, GeneratorError> {
+        let types = WeightedIndex::new(config.mix.weights())
+            .map_err(|err| GeneratorError::InvalidMix(err.to_string()))?;
+
+        if !(0.0..=1.0).contains(&config.invalid_fraction) {
+            return Err(GeneratorError::InvalidFraction(config.invalid_fraction));
+        }
+
+        if config.clients == 0 {
+            return Err(GeneratorError::NoClients);
+        }
+
+        if id::from_sequence::<ClientId>(config.clients).is_none() {
+            return Err(GeneratorError::IdOutOfRange("clients"));
+        }
+
+        if id::from_sequence::<TransactionId>(config.rows).is_none() {
+            return Err(GeneratorError::IdOutOfRange("rows"));
+        }
+
+        let clients = (0..config.clients)
+            .map(|_| ClientState::default())
+            .collect();
+
+        Ok(Self {
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            types,
+            clients,
+            active: (0..config.clients as usize).collect(),
+            generated: 0,
+            next_tx: 1,
+            config,
+        })
+    }
+
+    fn row(ty: TransactionType, client: usize, tx: u64, units: Option<i64>) -> RawTransactionData {
+        RawTransactionData {
+            id: id::from_sequence(tx).expect("Transaction ID in range"),
+            ty,
+            account_id: id::from_sequence(client as u64 + 1).expect("Client ID in range"),
+            amount: units.map(|units| Decimal::new(units, AMOUNT_DECIMAL_PLACES)),
+            timestamp: None,
+        }
+    }
+
+    /// Allocates a new transaction ID. There are at most as many of them as rows.
+    fn next_tx(&mut self) -> u64 {
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        tx
+    }
+
+    fn deposit(&mut self, client: usize) -> RawTransactionData {
+        let tx = self.next_tx();
+        let units = self.rng.gen_range(1..=MAX_DEPOSIT_UNITS);
+        let state = &mut self.clients[client];
+
+        state.available += units;
+        state.deposits.push(Deposit { tx, units });
+
+        Self::row(TransactionType::Deposit, client, tx, Some(units))
+    }
+
+    fn valid(&mut self, client: usize) -> RawTransactionData {
+        let ty = TYPES[self.types.sample(&mut self.rng)];
+        let state = &self.clients[client];
+
+        match ty {
+            TransactionType::Withdrawal if state.available > 0 => {
+                let units = self.rng.gen_range(1..=state.available);
+                let tx = self.next_tx();
+
+                self.clients[client].available -= units;
+
+                Self::row(ty, client, tx, Some(units))
+            }
+
+            TransactionType::Dispute if !state.deposits.is_empty() => {
+                let index = self.rng.gen_range(0..state.deposits.len());
+                let deposit = state.deposits[index];
+
+                // The disputed funds must still be available.
+                if deposit.units > state.available {
+                    return self.deposit(client);
+                }
+
+                let state = &mut self.clients[client];
+
+                state.deposits.swap_remove(index);
+                state.disputed.push(deposit);
+                state.available -= deposit.units;
+
+                Self::row(ty, client, deposit.tx, None)
+            }
+
+            // The last unlocked client isn't charged back, so that the valid rows can go on.
+            TransactionType::Resolve | TransactionType::Chargeback
+                if !state.disputed.is_empty()
+                    && (ty == TransactionType::Resolve || self.active.len() > 1) =>
+            {
+                let index = self.rng.gen_range(0..state.disputed.len());
+                let state = &mut self.clients[client];
+                let deposit = state.disputed.swap_remove(index);
+
+                if ty == TransactionType::Resolve {
+                    state.deposits.push(deposit);
+                    state.available += deposit.units;
+                } else {
+                    self.active.retain(|active| *active != client);
+                }
+
+                Self::row(ty, client, deposit.tx, None)
+            }
+
+            _ => self.deposit(client),
+        }
+    }
+
+    fn invalid(&mut self, client: usize) -> RawTransactionData {
+        let invalid = INVALID[self.rng.gen_range(0..INVALID.len())];
+        let state = &self.clients[client];
+
+        let deposit = (!state.deposits.is_empty())
+            .then(|| state.deposits[self.rng.gen_range(0..state.deposits.len())]);
+
+        match (invalid, deposit) {
+            (Invalid::InsufficientFunds, _) => {
+                let units = state.available + self.rng.gen_range(1..=MAX_DEPOSIT_UNITS);
+                let tx = self.next_tx();
+
+                Self::row(TransactionType::Withdrawal, client, tx, Some(units))
+            }
+
+            (Invalid::NotDisputed, Some(deposit)) => {
+                Self::row(TransactionType::Resolve, client, deposit.tx, None)
+            }
+
+            (Invalid::NonPositiveAmount, _) => {
+                let units = -self.rng.gen_range(0..=MAX_DEPOSIT_UNITS);
+                let tx = self.next_tx();
+
+                Self::row(TransactionType::Deposit, client, tx, Some(units))
+            }
+
+            // The different amount makes it a conflict rather than a redelivered duplicate.
+            (Invalid::IdConflict, Some(deposit)) => {
+                let units = deposit.units % MAX_DEPOSIT_UNITS + 1;
+
+                Self::row(TransactionType::Deposit, client, deposit.tx, Some(units))
+            }
+
+            // The ID is allocated, but never used by a transaction.
+            _ => {
+                let tx = self.next_tx();
+
+                Self::row(TransactionType::Dispute, client, tx, None)
+            }
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = RawTransactionData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.generated == self.config.rows {
+            return None;
+        }
+
+        self.generated += 1;
+
+        let client = self.active[self.rng.gen_range(0..self.active.len())];
+
+        if self.rng.gen_bool(self.config.invalid_fraction) {
+            Some(self.invalid(client))
+        } else {
+            Some(self.valid(client))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Generator, TypeMix, WorkloadConfig};
+    use crate::engine::{Engine, TransactionOutcome};
+    use crate::transaction::TransactionType;
+
+    fn config(invalid_fraction: f64, seed: u64) -> WorkloadConfig {
+        WorkloadConfig {
+            rows: 5000,
+            clients: 20,
+            invalid_fraction,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_rows() {
+        let mut engine = Engine::new();
+        let mut chargebacks = 0;
+
+        for row in Generator::new(config(0.0, 1)).unwrap() {
+            assert_eq!(
+                engine.process_transaction(&row).unwrap(),
+                TransactionOutcome::Applied,
+                "{row:?}"
+            );
+
+            if row.ty == TransactionType::Chargeback {
+                chargebacks += 1;
+            }
+        }
+
+        assert!(chargebacks > 0);
+        assert!(engine.audit().is_ok());
+    }
+
+    #[test]
+    fn invalid_rows() {
+        let mut engine = Engine::new();
+
+        let rejected = Generator::new(config(0.2, 2))
+            .unwrap()
+            .filter(|row| engine.process_transaction(row).is_err())
+            .count();
+
+        assert!((800..1200).contains(&rejected), "{rejected}");
+    }
+
+    #[test]
+    fn reproducibility() {
+        let rows = |seed| {
+            Generator::new(config(0.1, seed))
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rows(3), rows(3));
+        assert_ne!(rows(3), rows(4));
+    }
+
+    #[test]
+    fn type_mix() {
+        assert_eq!(
+            "deposit=9, withdrawal=1".parse::<TypeMix>().unwrap(),
+            TypeMix {
+                deposit: 9,
+                withdrawal: 1,
+                dispute: 0,
+                resolve: 0,
+                chargeback: 0,
+            }
+        );
+
+        assert!("deposit".parse::<TypeMix>().is_err());
+        assert!("transfer=1".parse::<TypeMix>().is_err());
+        assert!("deposit=0".parse::<TypeMix>().is_ok());
+        assert!(Generator::new(WorkloadConfig {
+            mix: "deposit=0".parse().unwrap(),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
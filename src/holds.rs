@@ -0,0 +1,426 @@
+use crate::config::ConfigError;
+use crate::id::{ClientId, TransactionId};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// Policy for automatic holds on large deposits.
+///
+/// A deposit exceeding the threshold is held right after it's applied, and released once either
+/// of the configured conditions is met.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepositHoldPolicy {
+    /// Deposits above this amount are held.
+    pub threshold: Decimal,
+
+    /// Releases the hold after the given number of subsequent transactions.
+    pub release_after_transactions: Option<u64>,
+
+    /// Releases the hold after the given number of seconds. Only applies to the inputs with
+    /// transaction timestamps.
+    pub release_after_seconds: Option<u64>,
+}
+
+impl DepositHoldPolicy {
+    /// Checks that the holds are released eventually.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.release_after_transactions.is_none() && self.release_after_seconds.is_none() {
+            return Err(ConfigError::InvalidConfigError {
+                section: "deposit_hold",
+                reason: "no release condition",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct PendingHold {
+    client: ClientId,
+    id: TransactionId,
+    sequence: u64,
+    timestamp: Option<u64>,
+}
+
+/// Keeps track of the active automatic deposit holds.
+///
+/// Only the oldest hold is checked for release, as the holds become due in the order they were
+/// placed: the sequence numbers are increasing, and the holds are placed at the engine clock (the
+/// latest timestamp seen) rather than the deposit timestamp, so the placement times never decrease
+/// even if the timestamps aren't chronological.
+#[derive(Clone)]
+pub(crate) struct DepositHolds {
+    policy: Option<DepositHoldPolicy>,
+    pending: VecDeque<PendingHold>,
+
+    /// Changes made to the pending holds since [`Self::begin`], if journaling.
+    journal: Option<HoldJournal>,
+}
+
+/// Holds are only placed at the back and released from the front of the queue, so the released
+/// holds and the number of the placed ones are enough to revert the changes.
+#[derive(Clone, Default)]
+struct HoldJournal {
+    released: Vec<PendingHold>,
+    placed: usize,
+}
+
+impl DepositHolds {
+    pub fn new(policy: Option<DepositHoldPolicy>) -> Self {
+        Self {
+            policy,
+            pending: VecDeque::new(),
+            journal: None,
+        }
+    }
+
+    /// Starts journaling the changes, so that they can be rolled back.
+    pub fn begin(&mut self) {
+        self.journal = Some(HoldJournal::default());
+    }
+
+    /// Keeps the changes made since [`Self::begin`].
+    pub fn commit(&mut self) {
+        self.journal = None;
+    }
+
+    /// Reverts the changes made since [`Self::begin`].
+    pub fn rollback(&mut self) {
+        if let Some(journal) = self.journal.take() {
+            // Restores the queue as it was with the placed holds appended, as even these may have
+            // been released already.
+            for hold in journal.released.into_iter().rev() {
+                self.pending.push_front(hold);
+            }
+
+            self.pending.truncate(self.pending.len() - journal.placed);
+        }
+    }
+
+    /// Checks whether a deposit of the amount should be held.
+    pub fn should_hold(&self, amount: Decimal) -> bool {
+        matches!(&self.policy, Some(policy) if amount > policy.threshold)
+    }
+
+    /// Registers a hold placed on the transaction `id` of the client at the sequence number and
+    /// the engine clock.
+    pub fn place(
+        &mut self,
+        client: ClientId,
+        id: TransactionId,
+        sequence: u64,
+        timestamp: Option<u64>,
+    ) {
+        self.pending.push_back(PendingHold {
+            client,
+            id,
+            sequence,
+            timestamp,
+        });
+
+        if let Some(journal) = &mut self.journal {
+            journal.placed += 1;
+        }
+    }
+
+    /// Removes the oldest hold that's due for release at the given sequence number and time,
+    /// returning its client and transaction IDs.
+    pub fn pop_due(
+        &mut self,
+        sequence: u64,
+        now: Option<u64>,
+    ) -> Option<(ClientId, TransactionId)> {
+        let policy = self.policy.as_ref()?;
+        let hold = self.pending.front()?;
+
+        let by_count = matches!(
+            policy.release_after_transactions,
+            Some(count) if sequence > hold.sequence.saturating_add(count)
+        );
+
+        let by_time = matches!(
+            (policy.release_after_seconds, hold.timestamp, now),
+            (Some(seconds), Some(placed), Some(now)) if now >= placed.saturating_add(seconds)
+        );
+
+        if !by_count && !by_time {
+            return None;
+        }
+
+        let hold = self.pending.pop_front()?;
+        let ids = (hold.client, hold.id);
+
+        if let Some(journal) = &mut self.journal {
+            journal.released.push(hold);
+        }
+
+        Some(ids)
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::DepositHoldPolicy;
+    use crate::account::AccountBalance;
+    use crate::config::ConfigError;
+    use crate::engine::EngineError;
+    use crate::testing::{account_balance, create_engine, create_input};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn release_after_transactions() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 2
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 100.0
+            deposit, 1, 2, 150.0
+            withdrawal, 1, 3, 150.0
+            withdrawal, 1, 4, 50.0
+            withdrawal, 1, 5, 150.0",
+        );
+
+        let test_balance = vec![
+            AccountBalance::with_amount(dec!(100.0), dec!(0.0))?,
+            AccountBalance::with_amount(dec!(250.0), dec!(150.0))?,
+            AccountBalance::with_amount(dec!(250.0), dec!(150.0))?,
+            AccountBalance::with_amount(dec!(200.0), dec!(150.0))?,
+            AccountBalance::with_amount(dec!(50.0), dec!(0.0))?,
+        ];
+
+        for (tx, balance) in std::iter::zip(&input, test_balance) {
+            // The third transaction is rejected, as the funds are still held.
+            let _ = engine.process_transaction(tx);
+
+            assert_eq!(account_balance(&engine, 1), balance);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn release_after_seconds() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_seconds = 60
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount, timestamp
+            deposit, 1, 1, 150.0, 1000
+            withdrawal, 1, 2, 50.0, 1059
+            withdrawal, 1, 3, 50.0, 1060",
+        );
+
+        engine.process_transaction(&input[0])?;
+
+        assert!(matches!(
+            engine.process_transaction(&input[1]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        engine.process_transaction(&input[2])?;
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(100.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn release_unordered_timestamps() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_seconds = 60
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount, timestamp
+            deposit, 1, 1, 150.0, 1000
+            deposit, 2, 2, 150.0, 900
+            withdrawal, 2, 3, 50.0, 1059
+            withdrawal, 2, 4, 50.0, 1060",
+        );
+
+        engine.process_transaction(&input[0])?;
+        engine.process_transaction(&input[1])?;
+
+        // The second deposit is held as of the latest timestamp seen, not its own.
+        assert!(matches!(
+            engine.process_transaction(&input[2]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        engine.process_transaction(&input[3])?;
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(150.0), dec!(0.0))?
+        );
+        assert_eq!(
+            account_balance(&engine, 2),
+            AccountBalance::with_amount(dec!(100.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_takes_over_hold() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 2
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 150.0
+            dispute, 1, 1
+            deposit, 1, 2, 10.0
+            deposit, 1, 3, 10.0
+            resolve, 1, 1",
+        );
+
+        for tx in &input {
+            engine.process_transaction(tx)?;
+        }
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(170.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_keeps_hold() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 100
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 150.0
+            dispute, 1, 1
+            resolve, 1, 1
+            withdrawal, 1, 2, 150.0",
+        );
+
+        for tx in &input[..3] {
+            engine.process_transaction(tx)?;
+        }
+
+        // The dispute is resolved before the hold is due, so the funds are still held.
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(150.0), dec!(150.0))?
+        );
+
+        assert!(matches!(
+            engine.process_transaction(&input[3]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+        assert!(engine.audit().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn chargeback_cancels_hold() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_transactions = 2
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 150.0
+            dispute, 1, 1
+            chargeback, 1, 1
+            deposit, 2, 2, 10.0",
+        );
+
+        for tx in &input {
+            engine.process_transaction(tx)?;
+        }
+
+        // The hold becoming due after the chargeback releases nothing.
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(0.0), dec!(0.0))?
+        );
+        assert!(engine.audit().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn policy_validation() -> anyhow::Result<()> {
+        let policy: DepositHoldPolicy = toml::from_str(r#"threshold = "100.0""#)?;
+
+        assert!(matches!(
+            policy.validate(),
+            Err(ConfigError::InvalidConfigError {
+                section: "deposit_hold",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn release_time_overflow() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [deposit_hold]
+            threshold = "100.0"
+            release_after_seconds = 60
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount, timestamp
+            deposit, 1, 1, 150.0, 18446744073709551600
+            withdrawal, 1, 2, 50.0, 18446744073709551614",
+        );
+
+        // The release time is out of the timestamp range, so the funds are held for good.
+        engine.process_transaction(&input[0])?;
+
+        assert!(matches!(
+            engine.process_transaction(&input[1]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        Ok(())
+    }
+}
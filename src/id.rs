@@ -0,0 +1,39 @@
+//! Client and transaction ID types.
+//!
+//! Both IDs are `u64` by default. The `narrow-ids` feature switches to the `u16` client and `u32`
+//! transaction IDs of the original input format, and the `uuid-ids` feature to UUIDs.
+
+#[cfg(all(feature = "narrow-ids", feature = "uuid-ids"))]
+compile_error!("features `narrow-ids` and `uuid-ids` are mutually exclusive");
+
+#[cfg(not(any(feature = "narrow-ids", feature = "uuid-ids")))]
+pub type ClientId = u64;
+
+#[cfg(not(any(feature = "narrow-ids", feature = "uuid-ids")))]
+pub type TransactionId = u64;
+
+#[cfg(feature = "narrow-ids")]
+pub type ClientId = u16;
+
+#[cfg(feature = "narrow-ids")]
+pub type TransactionId = u32;
+
+#[cfg(feature = "uuid-ids")]
+pub type ClientId = uuid::Uuid;
+
+#[cfg(feature = "uuid-ids")]
+pub type TransactionId = uuid::Uuid;
+
+/// Converts a sequence number into an ID, e.g. for the generated transactions. Returns `None` if
+/// the number doesn't fit into the ID type.
+#[cfg(not(feature = "uuid-ids"))]
+pub(crate) fn from_sequence<T: TryFrom<u64>>(n: u64) -> Option<T> {
+    T::try_from(n).ok()
+}
+
+/// Converts a sequence number into an ID, e.g. for the generated transactions. The number is
+/// stored in the lower half of the UUID.
+#[cfg(feature = "uuid-ids")]
+pub(crate) fn from_sequence<T: From<uuid::Uuid>>(n: u64) -> Option<T> {
+    Some(uuid::Uuid::from_u64_pair(0, n).into())
+}
@@ -1,9 +1,23 @@
-use crate::transaction::RawTransactionData;
+use crate::schema::{ColumnMapping, InputSchema};
+use crate::transaction::{RawTransactionData, TransactionType};
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
 use std::fs;
-use std::io;
+use std::io::{self, Read};
+use std::ops::Deref;
 use std::path::Path;
+use std::rc::Rc;
 use thiserror::Error as ThisError;
 
+/// Headers that must be present in the strict mode.
+const REQUIRED_HEADERS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Headers that may be present in the strict mode.
+const OPTIONAL_HEADERS: [&str; 1] = ["timestamp"];
+
 #[derive(Debug, ThisError)]
 pub enum InputStreamError {
     #[error(transparent)]
@@ -11,41 +25,647 @@ pub enum InputStreamError {
 
     #[error(transparent)]
     CsvError(#[from] csv::Error),
+
+    #[error("{position}: {}", describe_csv_error(.source))]
+    DecodeError {
+        position: RecordPosition,
+        source: csv::Error,
+    },
+
+    #[error("{position}: Unexpected extra columns: Expected={expected} Actual={actual}")]
+    ExtraColumnsError {
+        position: RecordPosition,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("{position}: Unknown transaction type: {ty}")]
+    UnknownTypeError {
+        position: RecordPosition,
+        ty: String,
+    },
+
+    #[error("{position}: Missing header: {header}")]
+    MissingHeaderError {
+        position: RecordPosition,
+        header: &'static str,
+    },
+
+    #[error("{position}: Unknown header: {header}")]
+    UnknownHeaderError {
+        position: RecordPosition,
+        header: String,
+    },
+}
+
+impl InputStreamError {
+    /// Short machine-readable name of the error kind, for aggregation purposes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::IoError(_) => "io_error",
+            Self::CsvError(_) | Self::DecodeError { .. } => "decoding_error",
+            Self::ExtraColumnsError { .. } => "extra_columns",
+            Self::UnknownTypeError { .. } => "unknown_transaction_type",
+            Self::MissingHeaderError { .. } => "missing_header",
+            Self::UnknownHeaderError { .. } => "unknown_header",
+        }
+    }
+
+    /// Returns the position of the record the error is related to, if any.
+    pub fn position(&self) -> Option<RecordPosition> {
+        match self {
+            Self::IoError(_) | Self::CsvError(_) => None,
+            Self::DecodeError { position, .. }
+            | Self::ExtraColumnsError { position, .. }
+            | Self::UnknownTypeError { position, .. }
+            | Self::MissingHeaderError { position, .. }
+            | Self::UnknownHeaderError { position, .. } => Some(*position),
+        }
+    }
+}
+
+/// Describes the CSV error without the position reported by `csv`, which doesn't account for
+/// the empty lines.
+fn describe_csv_error(err: &csv::Error) -> String {
+    match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => err.to_string(),
+    }
+}
+
+/// Position of a record in the input stream.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct RecordPosition {
+    /// Line number, starting at 1.
+    pub line: u64,
+
+    /// Byte offset of the start of the record, starting at 0.
+    pub byte: u64,
+}
+
+impl fmt::Display for RecordPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {} (byte {})", self.line, self.byte)
+    }
+}
+
+/// Transaction data decoded from the input stream, along with its position.
+///
+/// Dereferences to the `RawTransactionData`, so it can be passed to the engine directly.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InputRecord {
+    pub data: RawTransactionData,
+    pub position: RecordPosition,
+}
+
+impl Deref for InputRecord {
+    type Target = RawTransactionData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
 }
 
 /// A wrapper around `csv::Reader` to lose type parameters.
 ///
-/// Implements iterator for reading records directly from the data stream.
+/// Implements iterator for reading records directly from the data stream, and keeps track of
+/// the position of the last record read, so that the rows can be reported to the user.
+///
+/// By default, the rows are decoded leniently, e.g. the extra columns are ignored. In the strict
+/// mode, the headers are validated and the malformed rows are rejected.
+///
+/// The files not matching the standard headers can be read with an [`InputSchema`].
 pub struct InputStream {
+    /// Name of the source, e.g. the file path, used for reporting.
+    name: String,
+
     /// Boxed to potentially handle reading from other `std::io::Read` streams,
     /// e.g. `std::net::TcpStream`.
-    reader: Box<dyn Iterator<Item = csv::Result<RawTransactionData>>>,
+    reader: csv::Reader<Box<dyn io::Read>>,
+
+    blank_lines: Rc<RefCell<BlankLines>>,
+    columns: ColumnMapping,
+    record: csv::StringRecord,
+    strict: bool,
+
+    /// Position of the last record read.
+    position: RecordPosition,
 }
 
 impl InputStream {
     pub fn from_file<P: AsRef<Path>>(input_path: P) -> Result<Self, InputStreamError> {
-        Self::from_reader(fs::File::open(input_path)?)
+        Self::from_file_with_schema(input_path, InputSchema::default())
     }
 
+    pub fn from_file_with_schema<P: AsRef<Path>>(
+        input_path: P,
+        schema: InputSchema,
+    ) -> Result<Self, InputStreamError> {
+        let input_path = input_path.as_ref();
+
+        Ok(Self::decode(
+            fs::File::open(input_path)?,
+            schema,
+            Compression::from_extension(input_path),
+        )?
+        .with_name(input_path.display().to_string()))
+    }
+
+    /// Creates an input stream from the reader. The compressed (gzip, zstd or bzip2) data is
+    /// detected by its magic bytes and decompressed on the fly. The files are also detected by
+    /// their extension, if the magic bytes don't match any of the formats.
     pub fn from_reader<R: 'static + io::Read>(reader: R) -> Result<Self, InputStreamError> {
+        Self::from_reader_with_schema(reader, InputSchema::default())
+    }
+
+    /// Creates an input stream from the reader, mapping the columns with the schema.
+    pub fn from_reader_with_schema<R: 'static + io::Read>(
+        reader: R,
+        schema: InputSchema,
+    ) -> Result<Self, InputStreamError> {
+        Self::decode(reader, schema, Compression::None)
+    }
+
+    /// Creates an input stream from the data decompressed as detected by the magic bytes, or as
+    /// the `fallback` format if they don't match any.
+    fn decode<R: 'static + io::Read>(
+        mut reader: R,
+        schema: InputSchema,
+        fallback: Compression,
+    ) -> Result<Self, InputStreamError> {
+        // A single read may return fewer bytes than the magic bytes, e.g. from a pipe, so the
+        // reads are repeated until there are enough or the data ends.
+        let mut magic = Vec::with_capacity(Compression::MAGIC_LEN);
+        reader
+            .by_ref()
+            .take(Compression::MAGIC_LEN as u64)
+            .read_to_end(&mut magic)?;
+
+        let compression = match Compression::detect(&magic) {
+            Compression::None => fallback,
+            compression => compression,
+        };
+
+        let reader = io::BufReader::new(io::Cursor::new(magic).chain(reader));
+
+        let reader: Box<dyn io::Read> = match compression {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+            Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        };
+
+        let blank_lines = Rc::new(RefCell::new(BlankLines::default()));
+
+        let reader = LineTracker {
+            inner: reader,
+            blank_lines: blank_lines.clone(),
+        };
+
+        // The header row is read manually, as it is optional with the schema.
         let reader = csv::ReaderBuilder::new()
             .trim(csv::Trim::All)
             .flexible(true)
-            .from_reader(reader);
+            .has_headers(false)
+            .from_reader(Box::new(reader) as Box<dyn io::Read>);
+
+        let mut input = Self {
+            name: "<reader>".to_owned(),
+            reader,
+            blank_lines,
+            columns: ColumnMapping::new(&schema, &csv::StringRecord::new()),
+            record: csv::StringRecord::new(),
+            strict: false,
+            position: RecordPosition::default(),
+        };
+
+        let headers = match schema.columns {
+            Some(ref columns) => csv::StringRecord::from(columns.clone()),
+
+            None => {
+                let mut headers = csv::StringRecord::new();
+                input.reader.read_record(&mut headers)?;
+
+                // The headers are the first record, so the following ones will be positioned
+                // correctly.
+                if let Some(position) = headers.position() {
+                    input.position = input.locate(position);
+                }
+
+                headers
+            }
+        };
+
+        input.columns = ColumnMapping::new(&schema, &headers);
+
+        Ok(input)
+    }
+
+    /// Sets the name of the source used for reporting.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Switches the stream to the strict mode, validating the headers: all of the transaction
+    /// fields must be present, and no unknown columns are allowed.
+    pub fn strict(mut self) -> Result<Self, InputStreamError> {
+        for header in REQUIRED_HEADERS {
+            if !self.columns.headers().iter().any(|name| name == header) {
+                return Err(InputStreamError::MissingHeaderError {
+                    position: self.position,
+                    header,
+                });
+            }
+        }
+
+        let unknown = self
+            .columns
+            .headers()
+            .iter()
+            .find(|name| !REQUIRED_HEADERS.contains(name) && !OPTIONAL_HEADERS.contains(name));
+
+        if let Some(header) = unknown {
+            return Err(InputStreamError::UnknownHeaderError {
+                position: self.position,
+                header: header.to_owned(),
+            });
+        }
+
+        self.strict = true;
+
+        Ok(self)
+    }
+
+    /// Returns the name of the source.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the position of the last record read (or attempted to be read).
+    pub fn position(&self) -> RecordPosition {
+        self.position
+    }
+
+    /// Returns the actual position of the record positioned by `csv`.
+    fn locate(&self, position: &csv::Position) -> RecordPosition {
+        // The record position points at the empty lines preceding the record, if there are any,
+        // so these have to be skipped to get the actual position.
+        let (lines, byte) = self.blank_lines.borrow_mut().skip_from(position.byte());
+
+        RecordPosition {
+            line: position.line() + lines,
+            byte,
+        }
+    }
+
+    fn read_record(&mut self) -> Option<Result<InputRecord, InputStreamError>> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => {
+                if let Some(position) = self.record.position() {
+                    self.position = self.locate(position);
+                }
+
+                Some(self.decode_record())
+            }
+
+            Ok(false) => None,
+
+            Err(err) => {
+                self.position = match err.position() {
+                    Some(position) => self.locate(position),
+                    None => RecordPosition {
+                        line: self.position.line + 1,
+                        ..self.position
+                    },
+                };
 
-        Ok(Self {
-            reader: Box::new(reader.into_deserialize()),
-        })
+                Some(Err(InputStreamError::DecodeError {
+                    position: self.position,
+                    source: err,
+                }))
+            }
+        }
+    }
+
+    fn decode_record(&self) -> Result<InputRecord, InputStreamError> {
+        let position = self.position;
+
+        if self.strict && self.record.len() > self.columns.input_len() {
+            return Err(InputStreamError::ExtraColumnsError {
+                position,
+                expected: self.columns.input_len(),
+                actual: self.record.len(),
+            });
+        }
+
+        let record = self.columns.map(&self.record);
+
+        if self.strict {
+            if let Some(ty) = self.columns.transaction_type(&record) {
+                let deserializer = StrDeserializer::<ValueError>::new(ty);
+
+                if TransactionType::deserialize(deserializer).is_err() {
+                    return Err(InputStreamError::UnknownTypeError {
+                        position,
+                        ty: ty.to_owned(),
+                    });
+                }
+            }
+        }
+
+        let data = record
+            .deserialize(Some(self.columns.headers()))
+            .map_err(|source| InputStreamError::DecodeError { position, source })?;
+
+        Ok(InputRecord { data, position })
+    }
+}
+
+/// Compression format of the input data.
+#[derive(Debug, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Length of the longest magic bytes.
+    const MAGIC_LEN: usize = 4;
+
+    /// Detects the compression format by the magic bytes at the start of the data.
+    fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Self::Gzip
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Self::Zstd
+        } else if data.starts_with(b"BZh") {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+
+    /// Detects the compression format by the file extension.
+    fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("zst") => Self::Zstd,
+            Some("bz2") => Self::Bzip2,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Byte ranges of the empty lines read so far, which are skipped by `csv::Reader`.
+#[derive(Default)]
+struct BlankLines {
+    ranges: VecDeque<(u64, u64)>,
+    offset: u64,
+    line_start: u64,
+    line_has_data: bool,
+}
+
+impl BlankLines {
+    fn scan(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.offset += 1;
+
+            match byte {
+                b'\n' => {
+                    if !self.line_has_data {
+                        self.ranges.push_back((self.line_start, self.offset));
+                    }
+
+                    self.line_start = self.offset;
+                    self.line_has_data = false;
+                }
+
+                b'\r' => {}
+
+                _ => self.line_has_data = true,
+            }
+        }
+    }
+
+    /// Returns the number of consecutive empty lines starting at the byte offset and the offset
+    /// following them, discarding everything before.
+    fn skip_from(&mut self, offset: u64) -> (u64, u64) {
+        let mut cursor = offset;
+        let mut count = 0;
+
+        while let Some(&(start, end)) = self.ranges.front() {
+            if start < cursor {
+                self.ranges.pop_front();
+            } else if start == cursor {
+                self.ranges.pop_front();
+                cursor = end;
+                count += 1;
+            } else {
+                break;
+            }
+        }
+
+        (count, cursor)
+    }
+}
+
+/// Reader wrapper collecting the empty lines for `BlankLines`.
+struct LineTracker<R> {
+    inner: R,
+    blank_lines: Rc<RefCell<BlankLines>>,
+}
+
+impl<R: io::Read> io::Read for LineTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.blank_lines.borrow_mut().scan(&buf[..len]);
+        Ok(len)
     }
 }
 
 impl Iterator for InputStream {
-    type Item = Result<RawTransactionData, InputStreamError>;
+    type Item = Result<InputRecord, InputStreamError>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        self.reader
-            .next()
-            .map(|res| res.map_err(InputStreamError::from))
+        self.read_record()
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let csv_data = "type, client, tx, amount\n\
+                        deposit, 1, 1, 1.0\n\
+                        \r\n\
+                        deposit, 1, x, 1.0\n\
+                        withdrawal, 1, 2, 1.0";
+
+        let mut input = InputStream::from_reader(csv_data.as_bytes())
+            .unwrap()
+            .with_name("test.csv");
+
+        assert_eq!(input.name(), "test.csv");
+
+        let record = input.next().unwrap().unwrap();
+        assert_eq!(record.position, RecordPosition { line: 2, byte: 25 });
+        assert_eq!(input.position(), record.position);
+
+        // Empty lines are skipped, but still counted.
+        let err = input.next().unwrap().unwrap_err();
+        assert_eq!(err.position(), Some(RecordPosition { line: 4, byte: 46 }));
+        assert!(err.to_string().starts_with("Line 4 (byte 46): "));
+
+        let record = input.next().unwrap().unwrap();
+        assert_eq!(record.position, RecordPosition { line: 5, byte: 65 });
+
+        assert!(input.next().is_none());
+    }
+
+    #[test]
+    fn strict_mode() {
+        let csv_data = "type, client, tx, amount\n\
+                        deposit, 1, 1, 1.0\n\
+                        deposit, 1, 3, 5.0,\n\
+                        refund, 1, 4, 1.0";
+
+        // Extra columns are ignored and unknown types are only rejected by the decoder.
+        let results = InputStream::from_reader(csv_data.as_bytes())
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert!(results[1].is_ok());
+        assert!(matches!(
+            results[2],
+            Err(InputStreamError::DecodeError { .. })
+        ));
+
+        let results = InputStream::from_reader(csv_data.as_bytes())
+            .unwrap()
+            .strict()
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(InputStreamError::ExtraColumnsError {
+                position: RecordPosition { line: 3, .. },
+                expected: 4,
+                actual: 5,
+            })
+        ));
+        assert!(matches!(
+            &results[2],
+            Err(InputStreamError::UnknownTypeError { ty, .. }) if ty == "refund"
+        ));
+
+        let res = InputStream::from_reader("type, client, tx\ndeposit, 1, 1".as_bytes())
+            .unwrap()
+            .strict();
+
+        assert!(matches!(
+            res,
+            Err(InputStreamError::MissingHeaderError {
+                header: "amount",
+                ..
+            })
+        ));
+
+        let res = InputStream::from_reader("type, client, tx, amount, note\n".as_bytes())
+            .unwrap()
+            .strict();
+
+        assert!(matches!(
+            res,
+            Err(InputStreamError::UnknownHeaderError { header, .. }) if header == "note"
+        ));
+    }
+
+    #[test]
+    fn compressed_input() {
+        use std::io::Write;
+
+        let csv_data = "type, client, tx, amount\n\
+                        deposit, 1, 1, 1.0\n\
+                        withdrawal, 1, 2, 1.0\n";
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(csv_data.as_bytes()).unwrap();
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(csv_data.as_bytes()).unwrap();
+
+        let inputs = [
+            (Compression::None, csv_data.as_bytes().to_vec()),
+            (Compression::Gzip, gzip.finish().unwrap()),
+            (
+                Compression::Zstd,
+                zstd::encode_all(csv_data.as_bytes(), 0).unwrap(),
+            ),
+            (Compression::Bzip2, bzip2.finish().unwrap()),
+        ];
+
+        for (compression, data) in inputs {
+            assert_eq!(Compression::detect(&data), compression);
+
+            let rows = InputStream::from_reader(io::Cursor::new(data.clone()))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+
+            assert_eq!(rows.len(), 2);
+
+            // The magic bytes are detected even if they're split across reads.
+            let rows = InputStream::from_reader(OneByteReader(io::Cursor::new(data)))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+
+            assert_eq!(rows.len(), 2);
+        }
+    }
+
+    #[test]
+    fn compression_extension() {
+        assert_eq!(
+            Compression::from_extension(Path::new("input.csv.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("input.csv.zst")),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("input.csv.bz2")),
+            Compression::Bzip2
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("input.csv")),
+            Compression::None
+        );
+
+        // The data without matching magic bytes is decoded as the extension says, failing here.
+        let res = InputStream::decode("type".as_bytes(), InputSchema::default(), Compression::Gzip)
+            .and_then(|input| input.collect::<Result<Vec<_>, _>>());
+
+        assert!(res.is_err());
+    }
+
+    /// Reader returning a single byte per read.
+    struct OneByteReader<R>(R);
+
+    impl<R: io::Read> io::Read for OneByteReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
     }
 }
@@ -0,0 +1,266 @@
+use crate::account::{AccountBalance, BalanceOperationError};
+use crate::config::ConfigError;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+
+/// Number of decimal places interest is rounded to.
+const INTEREST_DECIMAL_PLACES: u32 = 4;
+
+/// Day count convention used to convert the accrual period into a fraction of a year.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum DayCount {
+    /// Actual/365 Fixed.
+    #[default]
+    #[serde(rename = "actual_365")]
+    Actual365,
+
+    /// Actual/360.
+    #[serde(rename = "actual_360")]
+    Actual360,
+}
+
+impl DayCount {
+    /// Number of days in a year according to the convention.
+    pub fn days_in_year(&self) -> Decimal {
+        match self {
+            DayCount::Actual365 => dec!(365),
+            DayCount::Actual360 => dec!(360),
+        }
+    }
+}
+
+/// Interest accrual policy, as specified in the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterestPolicy {
+    /// Annual interest rate as a fraction, i.e. `0.05` is 5%.
+    pub annual_rate: Decimal,
+
+    #[serde(default)]
+    pub day_count: DayCount,
+
+    /// Whether the held funds earn interest in addition to the available ones.
+    #[serde(default = "default_include_held")]
+    pub include_held: bool,
+}
+
+fn default_include_held() -> bool {
+    true
+}
+
+impl InterestPolicy {
+    /// Checks that the rate isn't negative, as the interest is posted as a deposit.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.annual_rate < Decimal::ZERO {
+            return Err(ConfigError::InvalidConfigError {
+                section: "interest",
+                reason: "negative annual rate",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the interest accrued on the balance over the number of days. Negative balances
+    /// don't accrue any interest.
+    ///
+    /// Fails with [`BalanceOperationError::AmountOverflow`] if the interest is out of the decimal
+    /// range, e.g. for an excessive number of days.
+    pub fn calculate(
+        &self,
+        balance: &AccountBalance,
+        days: Decimal,
+    ) -> Result<Decimal, BalanceOperationError> {
+        let principal = if self.include_held {
+            balance.total()
+        } else {
+            balance.available()
+        };
+
+        let interest = principal
+            .max(Decimal::ZERO)
+            .checked_mul(self.annual_rate)
+            .and_then(|interest| interest.checked_mul(days))
+            .ok_or(BalanceOperationError::AmountOverflow { amount: days })?
+            / self.day_count.days_in_year();
+
+        Ok(interest.round_dp(INTEREST_DECIMAL_PLACES))
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::InterestPolicy;
+    use crate::account::AccountBalance;
+    use crate::config::ConfigError;
+    use crate::engine::{Engine, EngineError, TransactionOutcome, ValidationError};
+    use crate::testing::{account_balance, create_engine, create_input};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn accrual() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [interest]
+            annual_rate = "0.0365"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 1000.0
+            deposit, 1, 2, 1000.0
+            dispute, 1, 2
+            accrue, 1, 3, 10
+            accrue, 1, 3, 10
+            accrue, 1, 3, 20
+            dispute, 1, 3
+            chargeback, 1, 3
+            accrue, 1, 4, 10
+            accrue, 2, 5, 10",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+        next()?;
+        next()?;
+
+        // 2000 * 0.0365 * 10 / 365, including the held funds.
+        assert_eq!(next()?, TransactionOutcome::Applied);
+        assert_eq!(next()?, TransactionOutcome::Duplicate);
+
+        // Reusing the ID for a different period is a conflict, even though the interest amount
+        // isn't part of the input.
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::TransactionIdConflict
+            ))
+        ));
+
+        // The interest can be reversed as any other deposit.
+        next()?;
+        next()?;
+
+        assert_eq!(next()?, TransactionOutcome::Skipped);
+
+        assert!(matches!(
+            next(),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::InvalidAccountId
+            ))
+        ));
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(2000.0), dec!(1000.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn day_count() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [interest]
+            annual_rate = "0.036"
+            day_count = "actual_360"
+            include_held = false
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 1000.0
+            deposit, 1, 2, 1000.0
+            dispute, 1, 2
+            accrue, 1, 3, 10",
+        );
+
+        for tx in &input {
+            engine.process_transaction(tx)?;
+        }
+
+        // 1000 * 0.036 * 10 / 360, excluding the held funds.
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(2001.0), dec!(1000.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn accrual_overflow() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [interest]
+            annual_rate = "1.0"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 1000000000000000000000.0
+            accrue, 1, 2, 100000000000.0",
+        );
+
+        engine.process_transaction(&input[0])?;
+
+        assert!(matches!(
+            engine.process_transaction(&input[1]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        assert_eq!(
+            account_balance(&engine, 1),
+            AccountBalance::with_amount(dec!(1000000000000000000000.0), dec!(0.0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn negative_rate() -> anyhow::Result<()> {
+        let policy: InterestPolicy = toml::from_str(r#"annual_rate = "-10.0""#)?;
+
+        assert!(matches!(
+            policy.validate(),
+            Err(ConfigError::InvalidConfigError {
+                section: "interest",
+                ..
+            })
+        ));
+
+        let policy: InterestPolicy = toml::from_str(r#"annual_rate = "0.0""#)?;
+
+        assert!(policy.validate().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn not_configured() {
+        let mut engine = Engine::new();
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 1000.0
+            accrue, 1, 2, 10",
+        );
+
+        engine.process_transaction(&input[0]).unwrap();
+
+        assert!(matches!(
+            engine.process_transaction(&input[1]),
+            Err(EngineError::InvalidTransactionData(
+                ValidationError::InterestNotConfigured
+            ))
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+//! Payment transaction engine.
+//!
+//! Processes a stream of client transactions (deposits, withdrawals, disputes, resolutions and
+//! chargebacks, along with the extensions described in the README), maintaining client account
+//! balances.
+//!
+//! ```
+//! use tx_toy::{Engine, InputStream, TransactionOutcome};
+//!
+//! # // The input uses numeric IDs.
+//! # #[cfg(not(feature = "uuid-ids"))] {
+//! let input = InputStream::from_reader(
+//!     "type, client, tx, amount\ndeposit, 1, 1, 10.0\nwithdrawal, 1, 2, 4.0".as_bytes(),
+//! )?;
+//!
+//! let mut engine = Engine::new();
+//!
+//! for record in input {
+//!     // Records carry their position in the input, and dereference to the transaction data.
+//!     let record = record?;
+//!
+//!     assert_eq!(engine.process_transaction(&record)?, TransactionOutcome::Applied);
+//! }
+//!
+//! let account = engine.account(1).unwrap();
+//!
+//! assert_eq!(account.balance().available(), rust_decimal_macros::dec!(6.0));
+//! # }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod account;
+mod audit;
+mod batch;
+pub mod config;
+pub mod diff;
+pub mod engine;
+pub mod fees;
+pub mod generate;
+mod holds;
+pub mod id;
+pub mod input;
+pub mod interest;
+pub mod reconcile;
+pub mod representment;
+mod rules;
+pub mod schema;
+pub mod snapshot;
+mod status;
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod testing;
+pub mod transaction;
+
+pub use crate::account::{AccountBalance, AccountData, AccountInfo, BalanceOperationError};
+pub use crate::audit::AuditError;
+pub use crate::config::{ConfigError, EngineConfig};
+pub use crate::diff::{DiffError, SnapshotDiff};
+pub use crate::engine::{Engine, EngineError, EngineResult, TransactionOutcome, ValidationError};
+pub use crate::generate::{Generator, GeneratorError, TypeMix, WorkloadConfig};
+pub use crate::holds::DepositHoldPolicy;
+pub use crate::id::{ClientId, TransactionId};
+pub use crate::input::{InputRecord, InputStream, InputStreamError, RecordPosition};
+pub use crate::reconcile::Discrepancy;
+pub use crate::rules::Rule;
+pub use crate::schema::InputSchema;
+pub use crate::snapshot::{Snapshot, SnapshotError};
+pub use crate::status::{DisputePolicy, StatusChange};
+pub use crate::transaction::{
+    IdScope, RawTransactionData, TransactionData, TransactionStatus, TransactionStoreError,
+    TransactionType,
+};
@@ -0,0 +1,172 @@
+use crate::account::AccountInfo;
+use crate::id::ClientId;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Difference between the resulting and the expected account balances.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Discrepancy {
+    /// The expected account doesn't exist.
+    MissingClient(AccountInfo),
+
+    /// The account is not expected to exist.
+    ExtraClient(AccountInfo),
+
+    /// A field of the account differs from the expected value.
+    Mismatch {
+        client: ClientId,
+        field: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Discrepancy {
+    /// The client the discrepancy concerns.
+    pub fn client(&self) -> ClientId {
+        match self {
+            Discrepancy::MissingClient(info) | Discrepancy::ExtraClient(info) => info.client,
+            Discrepancy::Mismatch { client, .. } => *client,
+        }
+    }
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::MissingClient(info) => write!(f, "Client {}: Missing", info.client),
+            Discrepancy::ExtraClient(info) => write!(f, "Client {}: Unexpected", info.client),
+            Discrepancy::Mismatch {
+                client,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Client {client}: {field} mismatch: Expected={expected} Actual={actual}"
+            ),
+        }
+    }
+}
+
+/// Compares the resulting account balances to the expected ones, returning the discrepancies
+/// ordered by client ID.
+///
+/// Amounts are compared numerically, so `1.5` matches `1.50`.
+pub fn reconcile<'a>(
+    actual: impl IntoIterator<Item = AccountInfo>,
+    expected: impl IntoIterator<Item = &'a AccountInfo>,
+) -> Vec<Discrepancy> {
+    let mut actual = actual
+        .into_iter()
+        .map(|info| (info.client, info))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut discrepancies = Vec::new();
+
+    for expected in expected {
+        let actual = match actual.remove(&expected.client) {
+            Some(actual) => actual,
+            None => {
+                discrepancies.push(Discrepancy::MissingClient(expected.clone()));
+                continue;
+            }
+        };
+
+        let fields = [
+            ("available", expected.available, actual.available),
+            ("held", expected.held, actual.held),
+            ("total", expected.total, actual.total),
+        ];
+
+        for (field, expected_value, actual_value) in fields {
+            if expected_value != actual_value {
+                discrepancies.push(Discrepancy::Mismatch {
+                    client: actual.client,
+                    field,
+                    expected: expected_value.to_string(),
+                    actual: actual_value.to_string(),
+                });
+            }
+        }
+
+        if expected.locked != actual.locked {
+            discrepancies.push(Discrepancy::Mismatch {
+                client: actual.client,
+                field: "locked",
+                expected: expected.locked.to_string(),
+                actual: actual.locked.to_string(),
+            });
+        }
+    }
+
+    discrepancies.extend(actual.into_values().map(Discrepancy::ExtraClient));
+
+    // Stable sort keeps the field order of the mismatches.
+    discrepancies.sort_by_key(Discrepancy::client);
+
+    discrepancies
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::{reconcile, Discrepancy};
+    use crate::testing::account_info;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn matching_balances() {
+        let actual = vec![
+            account_info(1, "1.5", "0", false),
+            account_info(2, "2", "1", true),
+        ];
+        let expected = vec![
+            account_info(2, "2.00", "1.0", true),
+            account_info(1, "1.50", "0", false),
+        ];
+
+        assert_eq!(reconcile(actual, &expected), vec![]);
+    }
+
+    #[test]
+    fn discrepancies() {
+        let actual = vec![
+            account_info(1, "1.5", "0", false),
+            account_info(2, "2", "1", false),
+            account_info(4, "1", "0", false),
+        ];
+
+        let expected = vec![
+            account_info(1, "1.5", "0", false),
+            account_info(2, "2", "0.5", true),
+            account_info(3, "1", "0", false),
+        ];
+
+        assert_eq!(
+            reconcile(actual, &expected),
+            vec![
+                Discrepancy::Mismatch {
+                    client: 2,
+                    field: "held",
+                    expected: dec!(0.5).to_string(),
+                    actual: dec!(1).to_string(),
+                },
+                Discrepancy::Mismatch {
+                    client: 2,
+                    field: "total",
+                    expected: dec!(2.5).to_string(),
+                    actual: dec!(3).to_string(),
+                },
+                Discrepancy::Mismatch {
+                    client: 2,
+                    field: "locked",
+                    expected: "true".to_owned(),
+                    actual: "false".to_owned(),
+                },
+                Discrepancy::MissingClient(account_info(3, "1", "0", false)),
+                Discrepancy::ExtraClient(account_info(4, "1", "0", false)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use serde::Deserialize;
+
+/// Defines whether a successful representment unlocks the account locked by the chargeback.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockPolicy {
+    /// The account stays locked.
+    #[default]
+    Never,
+
+    /// The account is always unlocked.
+    Always,
+
+    /// The account is unlocked, unless it has other charged back transactions.
+    IfNoOtherChargebacks,
+}
+
+/// Representment (chargeback reversal) policy, as specified in the configuration.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepresentmentPolicy {
+    pub unlock: UnlockPolicy,
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use crate::account::AccountBalance;
+    use crate::engine::{Engine, EngineError};
+    use crate::id::ClientId;
+    use crate::testing::{create_engine, create_input};
+    use crate::transaction::TransactionStatus;
+    use rust_decimal_macros::dec;
+
+    fn account_state(engine: &Engine, id: ClientId) -> (AccountBalance, bool) {
+        let account = engine
+            .accounts()
+            .find(|account| account.id() == id)
+            .unwrap();
+
+        (account.balance().clone(), account.locked())
+    }
+
+    const INPUT: &str = r"type, client, tx, amount
+        deposit, 1, 1, 10.0
+        deposit, 1, 2, 5.0
+        dispute, 1, 1
+        chargeback, 1, 1
+        dispute, 1, 2
+        chargeback, 1, 2
+        representment, 1, 1
+        chargeback_reversal, 1, 2";
+
+    #[test]
+    fn reinstate() -> anyhow::Result<()> {
+        let mut engine = Engine::new();
+        let input = create_input(INPUT);
+
+        for tx in &input[..4] {
+            engine.process_transaction(tx)?;
+        }
+
+        // The account is locked, so the second deposit can't be disputed anymore.
+        assert!(engine.process_transaction(&input[4]).is_err());
+
+        // Not charged back.
+        assert!(matches!(
+            engine.process_transaction(&input[7]),
+            Err(EngineError::InvalidTransactionStatus {
+                required: TransactionStatus::Cancelled,
+                actual: TransactionStatus::Ok,
+            })
+        ));
+
+        engine.process_transaction(&input[6])?;
+
+        assert_eq!(
+            account_state(&engine, 1),
+            (AccountBalance::with_amount(dec!(15.0), dec!(0.0))?, true)
+        );
+
+        // Already reinstated.
+        assert!(matches!(
+            engine.process_transaction(&input[6]),
+            Err(EngineError::InvalidTransactionStatus {
+                required: TransactionStatus::Cancelled,
+                actual: TransactionStatus::Reinstated,
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unlock_policy() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [representment]
+            unlock = "if_no_other_chargebacks"
+            "#,
+        );
+
+        let input = create_input(INPUT);
+
+        for tx in &input[..4] {
+            engine.process_transaction(tx)?;
+        }
+
+        engine.process_transaction(&input[6])?;
+
+        assert_eq!(
+            account_state(&engine, 1),
+            (AccountBalance::with_amount(dec!(15.0), dec!(0.0))?, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_chargebacks() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [representment]
+            unlock = "if_no_other_chargebacks"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            deposit, 1, 2, 5.0
+            dispute, 1, 1
+            chargeback, 1, 1
+            representment, 1, 1
+            dispute, 1, 2
+            chargeback, 1, 2
+            representment, 1, 2",
+        );
+
+        for tx in &input[..7] {
+            engine.process_transaction(tx)?;
+        }
+
+        assert_eq!(
+            account_state(&engine, 1),
+            (AccountBalance::with_amount(dec!(10.0), dec!(0.0))?, true)
+        );
+
+        // The reinstated first deposit doesn't count as charged back anymore.
+        engine.process_transaction(&input[7])?;
+
+        assert_eq!(
+            account_state(&engine, 1),
+            (AccountBalance::with_amount(dec!(15.0), dec!(0.0))?, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn atomic_representment() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [fees]
+            house_account = 0
+
+            [fees.deposit]
+            type = "flat"
+            amount = "0.5"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            deposit, 1, 2, 1.0
+            dispute, 1, 1
+            chargeback, 1, 1
+            withdrawal, 0, 3, 0.5
+            deposit, 0, 4, 50000000000000000000000000000.0
+            representment, 1, 1",
+        );
+
+        for tx in &input[..6] {
+            engine.process_transaction(tx)?;
+        }
+
+        // The fee charged once again can't be credited to the house account without rounding.
+        assert!(matches!(
+            engine.process_transaction(&input[6]),
+            Err(EngineError::BalanceOperationError(..))
+        ));
+
+        // 10 - 0.5 + 1 - 0.5 - 10 + 0.5.
+        assert_eq!(
+            account_state(&engine, 1),
+            (AccountBalance::with_amount(dec!(0.5), dec!(0.0))?, true)
+        );
+
+        assert_eq!(
+            engine.transaction(1).map(|tx| tx.status),
+            Some(TransactionStatus::Cancelled)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use crate::account::AccountData;
+use crate::batch::Journal;
+use crate::config::ConfigError;
+use crate::engine::ValidationError;
+use crate::id::ClientId;
+use crate::transaction::{RawTransactionData, TransactionType};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A risk rule evaluated before a transaction is applied.
+///
+/// Windows are measured in transactions processed by the engine, i.e. a window of `100` covers
+/// the last 100 input rows, regardless of their type or client.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Rule {
+    /// Rejects all transactions of the listed clients.
+    Blocklist { clients: HashSet<ClientId> },
+
+    /// Rejects withdrawals exceeding the amount.
+    MaxWithdrawal { amount: Decimal },
+
+    /// Limits the number and/or the total amount of withdrawals a client can make within
+    /// the window.
+    Velocity {
+        window: u64,
+        max_count: Option<usize>,
+        max_amount: Option<Decimal>,
+    },
+
+    /// Prevents the funds of the client's first deposit from being withdrawn until the window
+    /// has passed.
+    FirstDepositHold { window: u64 },
+}
+
+impl Rule {
+    /// Rule name, as reported in [`ValidationError::RuleViolation`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Blocklist { .. } => "blocklist",
+            Rule::MaxWithdrawal { .. } => "max_withdrawal",
+            Rule::Velocity { .. } => "velocity",
+            Rule::FirstDepositHold { .. } => "first_deposit_hold",
+        }
+    }
+
+    /// Checks that the rule limits anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Rule::Velocity {
+            max_count: None,
+            max_amount: None,
+            ..
+        } = self
+        {
+            return Err(ConfigError::InvalidConfigError {
+                section: "rules",
+                reason: "velocity rule without `max_count` or `max_amount`",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Evaluates the configured rules and keeps track of the per-client history they require.
+#[derive(Clone)]
+pub(crate) struct RuleEngine {
+    rules: Vec<Rule>,
+
+    /// Accepted withdrawals per client, as `(sequence, amount)` pairs. Only maintained if there's
+    /// a velocity rule, and pruned to the longest velocity window.
+    withdrawals: HashMap<ClientId, VecDeque<(u64, Decimal)>>,
+
+    /// First accepted deposit per client, as a `(sequence, amount)` pair.
+    first_deposits: HashMap<ClientId, (u64, Decimal)>,
+
+    withdrawals_journal: Journal<ClientId, VecDeque<(u64, Decimal)>>,
+    first_deposits_journal: Journal<ClientId, (u64, Decimal)>,
+}
+
+impl RuleEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            withdrawals: HashMap::new(),
+            first_deposits: HashMap::new(),
+            withdrawals_journal: Journal::new(),
+            first_deposits_journal: Journal::new(),
+        }
+    }
+
+    /// Starts journaling the history changes, so that they can be rolled back.
+    pub fn begin(&mut self) {
+        self.withdrawals_journal.start();
+        self.first_deposits_journal.start();
+    }
+
+    /// Keeps the history changes made since [`Self::begin`].
+    pub fn commit(&mut self) {
+        self.withdrawals_journal.commit();
+        self.first_deposits_journal.commit();
+    }
+
+    /// Reverts the history changes made since [`Self::begin`].
+    pub fn rollback(&mut self) {
+        self.withdrawals_journal.rollback(&mut self.withdrawals);
+        self.first_deposits_journal
+            .rollback(&mut self.first_deposits);
+    }
+
+    /// Checks the transaction with the sequence number `seq` against all of the rules, returning
+    /// an error for the first violated one.
+    pub fn check(
+        &self,
+        seq: u64,
+        data: &RawTransactionData,
+        account: Option<&AccountData>,
+    ) -> Result<(), ValidationError> {
+        for rule in &self.rules {
+            if !self.check_rule(rule, seq, data, account) {
+                return Err(ValidationError::RuleViolation { rule: rule.name() });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records a successfully applied transaction in the history used by the rules.
+    pub fn record(&mut self, seq: u64, data: &RawTransactionData) {
+        let amount = match data.amount {
+            Some(amount) => amount,
+            None => return,
+        };
+
+        match data.ty {
+            TransactionType::Deposit => {
+                self.first_deposits_journal
+                    .record(data.account_id, self.first_deposits.get(&data.account_id));
+
+                self.first_deposits
+                    .entry(data.account_id)
+                    .or_insert((seq, amount));
+            }
+
+            TransactionType::Withdrawal => {
+                let window = match self.max_velocity_window() {
+                    Some(window) => window,
+                    None => return,
+                };
+
+                self.withdrawals_journal
+                    .record(data.account_id, self.withdrawals.get(&data.account_id));
+
+                let history = self.withdrawals.entry(data.account_id).or_default();
+
+                while matches!(history.front(), Some((past, _)) if past.saturating_add(window) <= seq)
+                {
+                    history.pop_front();
+                }
+
+                history.push_back((seq, amount));
+            }
+
+            _ => {}
+        }
+    }
+
+    fn check_rule(
+        &self,
+        rule: &Rule,
+        seq: u64,
+        data: &RawTransactionData,
+        account: Option<&AccountData>,
+    ) -> bool {
+        if let Rule::Blocklist { clients } = rule {
+            return !clients.contains(&data.account_id);
+        }
+
+        // The remaining rules only concern withdrawals.
+        let amount = match (data.ty, data.amount) {
+            (TransactionType::Withdrawal, Some(amount)) => amount,
+            _ => return true,
+        };
+
+        match rule {
+            Rule::MaxWithdrawal { amount: max } => amount <= *max,
+
+            Rule::Velocity {
+                window,
+                max_count,
+                max_amount,
+            } => {
+                let (count, total) = self
+                    .withdrawals
+                    .get(&data.account_id)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(past, _)| past.saturating_add(*window) > seq)
+                    .fold((1, amount), |(count, total), (_, amount)| {
+                        (count + 1, total.saturating_add(*amount))
+                    });
+
+                max_count.is_none_or(|max| count <= max)
+                    && max_amount.is_none_or(|max| total <= max)
+            }
+
+            Rule::FirstDepositHold { window } => {
+                match (self.first_deposits.get(&data.account_id), account) {
+                    (Some((deposited, held)), Some(account))
+                        if deposited.saturating_add(*window) > seq =>
+                    {
+                        account.balance().available().saturating_sub(amount) >= *held
+                    }
+
+                    _ => true,
+                }
+            }
+
+            Rule::Blocklist { .. } => true,
+        }
+    }
+
+    fn max_velocity_window(&self) -> Option<u64> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match rule {
+                Rule::Velocity { window, .. } => Some(*window),
+                _ => None,
+            })
+            .max()
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::Rule;
+    use crate::config::ConfigError;
+    use crate::engine::{EngineError, ValidationError};
+    use crate::testing::{create_engine, create_input};
+
+    fn assert_violation(res: Result<impl std::fmt::Debug, EngineError>, name: &str) {
+        assert!(
+            matches!(
+                res,
+                Err(EngineError::InvalidTransactionData(ValidationError::RuleViolation { rule }))
+                    if rule == name
+            ),
+            "expected `{name}` violation, got {res:?}"
+        );
+    }
+
+    #[test]
+    fn blocklist_and_max_withdrawal() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [[rules]]
+            type = "blocklist"
+            clients = [2]
+
+            [[rules]]
+            type = "max_withdrawal"
+            amount = "10.0"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 50.0
+            deposit, 2, 2, 50.0
+            withdrawal, 1, 3, 10.0
+            withdrawal, 1, 4, 10.5",
+        );
+
+        engine.process_transaction(&input[0])?;
+        assert_violation(engine.process_transaction(&input[1]), "blocklist");
+        engine.process_transaction(&input[2])?;
+        assert_violation(engine.process_transaction(&input[3]), "max_withdrawal");
+
+        Ok(())
+    }
+
+    #[test]
+    fn velocity() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [[rules]]
+            type = "velocity"
+            window = 4
+            max_count = 2
+            max_amount = "15.0"
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 100.0
+            withdrawal, 1, 2, 5.0
+            withdrawal, 1, 3, 11.0
+            withdrawal, 1, 4, 5.0
+            withdrawal, 1, 5, 5.0
+            withdrawal, 1, 6, 10.0",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+        next()?;
+        // Exceeds the amount limit.
+        assert_violation(next(), "velocity");
+        next()?;
+        // Exceeds the count limit.
+        assert_violation(next(), "velocity");
+        // The first withdrawal is out of the window now.
+        next()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn first_deposit_hold() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [[rules]]
+            type = "first_deposit_hold"
+            window = 3
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            deposit, 1, 2, 5.0
+            withdrawal, 1, 3, 6.0
+            withdrawal, 1, 4, 6.0",
+        );
+
+        let mut input = input.iter();
+        let mut next = || engine.process_transaction(input.next().unwrap());
+
+        next()?;
+        next()?;
+        assert_violation(next(), "first_deposit_hold");
+        next()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn velocity_amount_overflow() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [[rules]]
+            type = "velocity"
+            window = 10
+            max_amount = "70000000000000000000000000000"
+            "#,
+        );
+
+        // The sum of the withdrawals within the window is out of the decimal range.
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 50000000000000000000000000000.0
+            withdrawal, 1, 2, 50000000000000000000000000000.0
+            deposit, 1, 3, 50000000000000000000000000000.0
+            withdrawal, 1, 4, 50000000000000000000000000000.0",
+        );
+
+        for tx in &input[..3] {
+            engine.process_transaction(tx)?;
+        }
+
+        assert_violation(engine.process_transaction(&input[3]), "velocity");
+
+        Ok(())
+    }
+
+    #[test]
+    fn rule_validation() -> anyhow::Result<()> {
+        // Typos are rejected rather than ignored.
+        assert!(toml::from_str::<Rule>(
+            r#"
+            type = "velocity"
+            window = 10
+            max_cuont = 1
+            "#,
+        )
+        .is_err());
+
+        let rule: Rule = toml::from_str(
+            r#"
+            type = "velocity"
+            window = 10
+            "#,
+        )?;
+
+        assert!(matches!(
+            rule.validate(),
+            Err(ConfigError::InvalidConfigError {
+                section: "rules",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
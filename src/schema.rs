@@ -0,0 +1,204 @@
+use crate::config::{self, ConfigError};
+use crate::transaction::TransactionType;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Column layout of the input files, for the files not matching the standard
+/// `type, client, tx, amount` headers.
+///
+/// The mapping is applied before the rows are deserialized: the columns are named either by the
+/// header row or by `columns` for the files without one, then the `ignore`d columns are dropped,
+/// and the remaining ones are renamed with `rename`. The transaction type names are translated
+/// with `types`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputSchema {
+    /// Column names by position, for the files without the header row.
+    pub columns: Option<Vec<String>>,
+
+    /// Input column names mapped to the transaction fields, e.g. `"Client ID" = "client"`.
+    pub rename: HashMap<String, String>,
+
+    /// Input columns to be dropped.
+    pub ignore: Vec<String>,
+
+    /// Custom transaction type names, e.g. `DEP = "deposit"`.
+    pub types: HashMap<String, TransactionType>,
+}
+
+impl InputSchema {
+    /// Loads the schema from a TOML or JSON file. See [`config::load_file`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        config::load_file(path)
+    }
+}
+
+/// The schema resolved against the headers of a particular input stream.
+pub(crate) struct ColumnMapping {
+    /// Mapped headers, without the ignored columns.
+    headers: csv::StringRecord,
+
+    /// Number of the input columns.
+    input_len: usize,
+
+    /// Indices of the input columns kept, if any are ignored.
+    kept: Option<Vec<usize>>,
+
+    /// Index of the transaction type in the mapped headers.
+    type_index: Option<usize>,
+
+    types: HashMap<String, TransactionType>,
+}
+
+impl ColumnMapping {
+    pub(crate) fn new(schema: &InputSchema, headers: &csv::StringRecord) -> Self {
+        let kept = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| !schema.ignore.iter().any(|ignored| ignored == name))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let mapped = kept
+            .iter()
+            .map(|&index| {
+                let name = &headers[index];
+                schema.rename.get(name).map_or(name, String::as_str)
+            })
+            .collect::<csv::StringRecord>();
+
+        Self {
+            type_index: mapped.iter().position(|name| name == "type"),
+            headers: mapped,
+            input_len: headers.len(),
+            kept: (kept.len() < headers.len()).then_some(kept),
+            types: schema.types.clone(),
+        }
+    }
+
+    /// Mapped headers, to deserialize the mapped records with.
+    pub(crate) fn headers(&self) -> &csv::StringRecord {
+        &self.headers
+    }
+
+    /// Number of the input columns, including the ignored ones.
+    pub(crate) fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    /// Returns the transaction type name of the mapped record.
+    pub(crate) fn transaction_type<'a>(&self, record: &'a csv::StringRecord) -> Option<&'a str> {
+        self.type_index.and_then(|index| record.get(index))
+    }
+
+    /// Maps the input record, dropping the ignored columns and translating the type names. The
+    /// record is only copied if anything has to be changed.
+    pub(crate) fn map<'a>(&self, record: &'a csv::StringRecord) -> Cow<'a, csv::StringRecord> {
+        let mapped = match &self.kept {
+            Some(kept) => Cow::Owned(kept.iter().filter_map(|&index| record.get(index)).collect()),
+            None => Cow::Borrowed(record),
+        };
+
+        let ty = self
+            .transaction_type(&mapped)
+            .and_then(|ty| self.types.get(ty))
+            .copied();
+
+        match (self.type_index, ty) {
+            (Some(type_index), Some(ty)) => Cow::Owned(
+                mapped
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        if index == type_index {
+                            ty.name()
+                        } else {
+                            field
+                        }
+                    })
+                    .collect(),
+            ),
+
+            _ => mapped,
+        }
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use super::*;
+    use crate::input::InputStream;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn header_mapping() {
+        let schema: InputSchema = toml::from_str(
+            r#"
+            ignore = ["note"]
+
+            [rename]
+            "Kind" = "type"
+            "Client ID" = "client"
+            "Ref" = "tx"
+            "Value" = "amount"
+
+            [types]
+            DEP = "deposit"
+            WD = "withdrawal"
+            "#,
+        )
+        .unwrap();
+
+        let csv_data = "Client ID, note, Kind, Ref, Value\n\
+                        1, first, DEP, 1, 10.0\n\
+                        1, , WD, 2, 4.0\n\
+                        1, , dispute, 1,";
+
+        let rows = InputStream::from_reader_with_schema(csv_data.as_bytes(), schema)
+            .unwrap()
+            .strict()
+            .unwrap()
+            .map(|record| record.unwrap().data)
+            .collect::<Vec<_>>();
+
+        assert_eq!(rows[0].ty, TransactionType::Deposit);
+        assert_eq!(rows[0].account_id, 1);
+        assert_eq!(rows[0].amount, Some(dec!(10.0)));
+        assert_eq!(rows[1].ty, TransactionType::Withdrawal);
+        assert_eq!(rows[1].id, 2);
+        assert_eq!(rows[2].ty, TransactionType::Dispute);
+        assert_eq!(rows[2].amount, None);
+    }
+
+    #[test]
+    fn positional_mapping() {
+        let schema = InputSchema {
+            columns: Some(vec![
+                "client".to_owned(),
+                "tx".to_owned(),
+                "type".to_owned(),
+                "amount".to_owned(),
+            ]),
+            ..Default::default()
+        };
+
+        let csv_data = "1, 1, deposit, 10.0\n\
+                        2, 2, deposit, 5.0";
+
+        let mut input = InputStream::from_reader_with_schema(csv_data.as_bytes(), schema).unwrap();
+
+        let record = input.next().unwrap().unwrap();
+        assert_eq!(record.position.line, 1);
+        assert_eq!(record.account_id, 1);
+        assert_eq!(record.amount, Some(dec!(10.0)));
+
+        let record = input.next().unwrap().unwrap();
+        assert_eq!(record.position.line, 2);
+        assert_eq!(record.account_id, 2);
+
+        assert!(input.next().is_none());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::account::AccountInfo;
+use crate::id::{ClientId, TransactionId};
+use crate::transaction::{StoredTransactionType, TransactionData, TransactionStatus};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+#[allow(clippy::enum_variant_names)]
+pub enum SnapshotError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// A stored transaction, as recorded in a snapshot.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransactionInfo {
+    pub client: ClientId,
+    pub tx: TransactionId,
+
+    #[serde(rename = "type")]
+    pub ty: StoredTransactionType,
+
+    pub amount: Decimal,
+    pub status: TransactionStatus,
+}
+
+impl From<&TransactionData> for TransactionInfo {
+    fn from(tx: &TransactionData) -> Self {
+        Self {
+            client: tx.account_id,
+            tx: tx.id,
+            ty: tx.ty,
+            amount: tx.amount,
+            status: tx.status,
+        }
+    }
+}
+
+/// Engine state at a point in time: the account balances and the stored transactions, ordered by
+/// the client and transaction IDs.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub accounts: Vec<AccountInfo>,
+
+    /// Not available for the snapshots loaded from account dumps.
+    pub transactions: Option<Vec<TransactionInfo>>,
+}
+
+impl Snapshot {
+    /// Creates a snapshot, sorting the accounts and transactions.
+    pub fn new(
+        mut accounts: Vec<AccountInfo>,
+        mut transactions: Option<Vec<TransactionInfo>>,
+    ) -> Self {
+        accounts.sort_by_key(|info| info.client);
+
+        if let Some(transactions) = &mut transactions {
+            transactions.sort_by_key(|info| (info.client, info.tx));
+        }
+
+        Self {
+            accounts,
+            transactions,
+        }
+    }
+
+    /// Loads a snapshot from a file. The format is detected by the file extension: files ending
+    /// with `.json` are parsed as JSON snapshots, everything else is parsed as CSV account dumps,
+    /// as printed by the `process` command.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        let path = path.as_ref();
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let snapshot: Snapshot =
+                serde_json::from_reader(io::BufReader::new(File::open(path)?))?;
+            return Ok(Self::new(snapshot.accounts, snapshot.transactions));
+        }
+
+        let accounts = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?
+            .deserialize()
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::new(accounts, None))
+    }
+
+    /// Writes the snapshot as JSON.
+    pub fn write_json<W: io::Write>(&self, writer: W) -> Result<(), SnapshotError> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::engine::EngineError;
+use crate::transaction::{RawTransactionData, TransactionData, TransactionStatus, TransactionType};
+use serde::Deserialize;
+
+/// Transactions changing the status of a stored transaction, along with the status they require
+/// and the status they result in.
+const TRANSITIONS: &[(TransactionType, TransactionStatus, TransactionStatus)] = &[
+    (
+        TransactionType::Dispute,
+        TransactionStatus::Ok,
+        TransactionStatus::UnderDispute,
+    ),
+    (
+        TransactionType::Resolve,
+        TransactionStatus::UnderDispute,
+        TransactionStatus::Ok,
+    ),
+    (
+        TransactionType::Chargeback,
+        TransactionStatus::UnderDispute,
+        TransactionStatus::Cancelled,
+    ),
+    (
+        TransactionType::Representment,
+        TransactionStatus::Cancelled,
+        TransactionStatus::Reinstated,
+    ),
+];
+
+/// Recorded change of a transaction status.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StatusChange {
+    pub from: TransactionStatus,
+    pub to: TransactionStatus,
+
+    /// Input row that caused the change.
+    pub cause: RawTransactionData,
+}
+
+/// Dispute limits, as specified in the configuration.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisputePolicy {
+    /// Maximum number of times a transaction can be disputed. Unlimited by default.
+    pub max_disputes: Option<usize>,
+}
+
+/// Transaction status state machine.
+///
+/// Drives all of the dispute-related status changes according to the transition table and
+/// the configured limits.
+#[derive(Clone)]
+pub(crate) struct StatusMachine {
+    policy: DisputePolicy,
+}
+
+impl StatusMachine {
+    pub fn new(policy: DisputePolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Returns the status the transaction moves to as the result of the input row, or an error if
+    /// such transition is not allowed.
+    pub fn transition(
+        &self,
+        tx: &TransactionData,
+        data: &RawTransactionData,
+    ) -> Result<TransactionStatus, EngineError> {
+        let (_, required, next) = TRANSITIONS
+            .iter()
+            .find(|(ty, ..)| *ty == data.ty)
+            .ok_or(EngineError::InternalError)?;
+
+        if tx.status != *required {
+            return Err(EngineError::InvalidTransactionStatus {
+                required: *required,
+                actual: tx.status,
+            });
+        }
+
+        if let Some(limit) = self.policy.max_disputes {
+            let disputes = tx
+                .history
+                .iter()
+                .filter(|change| change.to == TransactionStatus::UnderDispute)
+                .count();
+
+            if *next == TransactionStatus::UnderDispute && disputes >= limit {
+                return Err(EngineError::DisputeLimitExceeded { limit });
+            }
+        }
+
+        Ok(*next)
+    }
+}
+
+// The tests use numeric IDs.
+#[cfg(all(test, not(feature = "uuid-ids")))]
+mod test {
+    use crate::engine::EngineError;
+    use crate::testing::{create_engine, create_input};
+    use crate::transaction::TransactionStatus;
+
+    #[test]
+    fn dispute_limit() -> anyhow::Result<()> {
+        let mut engine = create_engine(
+            r#"
+            [disputes]
+            max_disputes = 1
+            "#,
+        );
+
+        let input = create_input(
+            r"type, client, tx, amount
+            deposit, 1, 1, 10.0
+            dispute, 1, 1
+            resolve, 1, 1
+            dispute, 1, 1",
+        );
+
+        for tx in &input[..3] {
+            engine.process_transaction(tx)?;
+        }
+
+        assert!(matches!(
+            engine.process_transaction(&input[3]),
+            Err(EngineError::DisputeLimitExceeded { limit: 1 })
+        ));
+
+        let history = engine.transaction(1).unwrap().history.clone();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].from, TransactionStatus::Ok);
+        assert_eq!(history[0].to, TransactionStatus::UnderDispute);
+        assert_eq!(history[0].cause, input[1]);
+        assert_eq!(history[1].from, TransactionStatus::UnderDispute);
+        assert_eq!(history[1].to, TransactionStatus::Ok);
+        assert_eq!(history[1].cause, input[2]);
+
+        Ok(())
+    }
+}
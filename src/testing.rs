@@ -0,0 +1,46 @@
+//! Fixtures shared by the unit tests.
+
+use crate::account::{AccountBalance, AccountInfo};
+use crate::config::EngineConfig;
+use crate::engine::Engine;
+use crate::id::ClientId;
+use crate::input::InputStream;
+use crate::transaction::RawTransactionData;
+
+/// Creates an engine with the TOML configuration, which must be valid.
+pub(crate) fn create_engine(config: &str) -> Engine {
+    let config = toml::from_str::<EngineConfig>(config).unwrap();
+    config.validate().unwrap();
+
+    Engine::with_config(config)
+}
+
+/// Decodes the CSV input, all rows of which must be valid.
+pub(crate) fn create_input(csv_data: &'static str) -> Vec<RawTransactionData> {
+    let test_input = InputStream::from_reader(csv_data.as_bytes()).unwrap();
+    test_input.map(|tx| tx.unwrap().data).collect::<Vec<_>>()
+}
+
+/// Returns the balance of the client's account, which must exist.
+pub(crate) fn account_balance(engine: &Engine, id: ClientId) -> AccountBalance {
+    engine.account(id).unwrap().balance().clone()
+}
+
+/// Creates the account info with the total of the available and held amounts.
+pub(crate) fn account_info(
+    client: ClientId,
+    available: &str,
+    held: &str,
+    locked: bool,
+) -> AccountInfo {
+    let available = available.parse().unwrap();
+    let held = held.parse().unwrap();
+
+    AccountInfo {
+        client,
+        available,
+        held,
+        total: available + held,
+        locked,
+    }
+}
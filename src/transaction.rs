@@ -1,6 +1,10 @@
+use crate::batch::Journal;
+use crate::id::{ClientId, TransactionId};
+use crate::status::StatusChange;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -21,34 +25,106 @@ pub enum TransactionType {
     Dispute,
     Resolve,
     Chargeback,
+
+    /// Administrative transaction setting the account's credit limit to the amount.
+    #[serde(rename = "credit_limit")]
+    CreditLimit,
+
+    /// Control transaction accruing interest on the account over the number of days specified in
+    /// the amount.
+    Accrue,
+
+    /// Reversal of a chargeback, re-crediting the charged back amount.
+    #[serde(alias = "chargeback_reversal")]
+    Representment,
+
+    /// Marker row opening a batch of transactions applied atomically. The transaction ID is used
+    /// as the batch ID.
+    Begin,
+
+    /// Marker row committing the batch opened by `begin` with the same transaction ID.
+    Commit,
+}
+
+impl TransactionType {
+    /// Transaction type name, as it appears in the input stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::CreditLimit => "credit_limit",
+            TransactionType::Accrue => "accrue",
+            TransactionType::Representment => "representment",
+            TransactionType::Begin => "begin",
+            TransactionType::Commit => "commit",
+        }
+    }
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 /// Raw transaction data coming from input stream.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct RawTransactionData {
     #[serde(rename = "tx")]
-    pub id: u32,
+    pub id: TransactionId,
 
     #[serde(rename = "type")]
     pub ty: TransactionType,
 
     #[serde(rename = "client")]
-    pub account_id: u16,
+    pub account_id: ClientId,
 
     pub amount: Option<Decimal>,
+
+    /// Optional Unix timestamp in seconds. Transactions are expected to come in chronological
+    /// order if the timestamps are present.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionStatus {
     Ok,
     UnderDispute,
     Cancelled,
+
+    /// Charged back transaction has been reinstated by a representment.
+    Reinstated,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// State of an automatic hold placed on a large deposit. Tracked separately from the dispute
+/// status, as both holds are independent of each other.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum HoldStatus {
+    Active,
+    Released,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum StoredTransactionType {
     Deposit,
     Withdrawal,
+
+    /// Interest posted by an accrual. Behaves as a deposit.
+    Interest,
+}
+
+impl StoredTransactionType {
+    /// Checks whether the transaction credits the account, and can therefore be disputed.
+    #[inline]
+    pub fn is_credit(&self) -> bool {
+        matches!(self, Self::Deposit | Self::Interest)
+    }
 }
 
 impl TryFrom<TransactionType> for StoredTransactionType {
@@ -58,67 +134,179 @@ impl TryFrom<TransactionType> for StoredTransactionType {
         match value {
             TransactionType::Deposit => Ok(Self::Deposit),
             TransactionType::Withdrawal => Ok(Self::Withdrawal),
+            TransactionType::Accrue => Ok(Self::Interest),
             value => Err(TransactionStoreError::InvalidType(value)),
         }
     }
 }
 
 /// Processed transaction data, as stored in the database.
+#[derive(Debug, Clone)]
 pub struct TransactionData {
-    pub id: u32,
+    pub id: TransactionId,
     pub ty: StoredTransactionType,
-    pub account_id: u16,
+    pub account_id: ClientId,
     pub amount: Decimal,
     pub status: TransactionStatus,
+    pub hold: Option<HoldStatus>,
+
+    /// Fee charged for the transaction itself. Refunded if the transaction is charged back.
+    pub fee: Decimal,
+
+    /// Fee charged for the chargeback of the transaction.
+    pub chargeback_fee: Decimal,
+
+    /// Accrual period in days requested by the input row of an interest transaction.
+    pub days: Option<Decimal>,
+
+    /// Every status change the transaction went through, oldest first.
+    pub history: Vec<StatusChange>,
+}
+
+impl TransactionData {
+    /// Checks whether the raw transaction data describes exactly this transaction, i.e. it's a
+    /// redelivery of the row this transaction was created from.
+    pub fn matches(&self, data: &RawTransactionData) -> bool {
+        self.id == data.id
+            && self.account_id == data.account_id
+            && StoredTransactionType::try_from(data.ty).is_ok_and(|ty| ty == self.ty)
+            // Interest amount is calculated by the engine, so the input is compared to the days.
+            && data.amount == Some(self.days.unwrap_or(self.amount))
+    }
+
+    /// Changes the transaction status, recording the change and the input row that caused it.
+    pub(crate) fn change_status(&mut self, status: TransactionStatus, cause: &RawTransactionData) {
+        self.history.push(StatusChange {
+            from: self.status,
+            to: status,
+            cause: cause.clone(),
+        });
+
+        self.status = status;
+    }
+
+    /// Amount the client has actually been credited with for a deposit, i.e. net of the fee.
+    #[inline]
+    pub fn net_amount(&self) -> Decimal {
+        self.amount - self.fee
+    }
 }
 
 impl TryFrom<&RawTransactionData> for TransactionData {
     type Error = TransactionStoreError;
 
     fn try_from(value: &RawTransactionData) -> Result<Self, Self::Error> {
+        let ty = value.ty.try_into()?;
+        let amount = value
+            .amount
+            .ok_or(TransactionStoreError::AmountNotAvailable)?;
+
         Ok(Self {
             id: value.id,
-            ty: value.ty.try_into()?,
+            ty,
             account_id: value.account_id,
-            amount: value
-                .amount
-                .ok_or(TransactionStoreError::AmountNotAvailable)?,
+            amount,
             status: TransactionStatus::Ok,
+            hold: None,
+            fee: Decimal::ZERO,
+            chargeback_fee: Decimal::ZERO,
+            days: (ty == StoredTransactionType::Interest).then_some(amount),
+            history: Vec::new(),
         })
     }
 }
 
+/// Defines the namespace the transaction IDs are unique in.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScope {
+    /// Transaction IDs are globally unique.
+    #[default]
+    Global,
+
+    /// Transaction IDs are unique per client, e.g. `deposit, 1, 1` and `deposit, 2, 1` are
+    /// different transactions.
+    Client,
+}
+
 /// Transaction database.
 ///
-/// NOTE: Assumes that transaction IDs are globally unique.
-pub struct TransactionStore {
-    data: HashMap<u32, TransactionData>,
+/// Transactions are looked up by the client and transaction IDs, the client ID being part of
+/// the key only in the [`IdScope::Client`] scope.
+#[derive(Clone)]
+pub(crate) struct TransactionStore {
+    scope: IdScope,
+    data: HashMap<(Option<ClientId>, TransactionId), TransactionData>,
+
+    /// Changes of the open batch, if there's one.
+    journal: Journal<(Option<ClientId>, TransactionId), TransactionData>,
 }
 
 impl TransactionStore {
-    pub fn new() -> Self {
+    pub fn new(scope: IdScope) -> Self {
         Self {
+            scope,
             data: HashMap::new(),
+            journal: Journal::new(),
+        }
+    }
+
+    #[inline]
+    fn key(&self, client: ClientId, id: TransactionId) -> (Option<ClientId>, TransactionId) {
+        match self.scope {
+            IdScope::Global => (None, id),
+            IdScope::Client => (Some(client), id),
         }
     }
 
     #[inline]
-    pub fn exists(&self, id: u32) -> bool {
-        self.data.contains_key(&id)
+    pub fn exists(&self, client: ClientId, id: TransactionId) -> bool {
+        self.data.contains_key(&self.key(client, id))
     }
 
     #[inline]
-    pub fn get(&self, id: u32) -> Option<&TransactionData> {
-        self.data.get(&id)
+    pub fn get(&self, client: ClientId, id: TransactionId) -> Option<&TransactionData> {
+        self.data.get(&self.key(client, id))
     }
 
+    /// Looks the transaction up by its ID only, which finds nothing in the [`IdScope::Client`]
+    /// scope.
     #[inline]
-    pub fn get_mut(&mut self, id: u32) -> Option<&mut TransactionData> {
-        self.data.get_mut(&id)
+    pub fn get_global(&self, id: TransactionId) -> Option<&TransactionData> {
+        self.data.get(&(None, id))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, client: ClientId, id: TransactionId) -> Option<&mut TransactionData> {
+        let key = self.key(client, id);
+        self.journal.record(key, self.data.get(&key));
+        self.data.get_mut(&key)
     }
 
     #[inline]
     pub fn insert(&mut self, data: TransactionData) {
-        self.data.insert(data.id, data);
+        let key = self.key(data.account_id, data.id);
+        self.journal.record(key, self.data.get(&key));
+        self.data.insert(key, data);
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &TransactionData> {
+        self.data.values()
+    }
+
+    /// Starts journaling the changes, so that they can be rolled back.
+    pub fn begin(&mut self) {
+        self.journal.start();
+    }
+
+    /// Keeps the changes made since [`Self::begin`].
+    pub fn commit(&mut self) {
+        self.journal.commit();
+    }
+
+    /// Reverts the changes made since [`Self::begin`].
+    pub fn rollback(&mut self) {
+        self.journal.rollback(&mut self.data);
     }
 }
@@ -0,0 +1,145 @@
+// The inputs use numeric IDs.
+#![cfg(not(feature = "uuid-ids"))]
+
+use rust_decimal_macros::dec;
+use tx_toy::{
+    AccountInfo, Engine, EngineConfig, EngineError, InputStream, TransactionOutcome,
+    TransactionStatus, ValidationError,
+};
+
+fn process(
+    engine: &mut Engine,
+    csv_data: &'static str,
+) -> Vec<Result<TransactionOutcome, EngineError>> {
+    InputStream::from_reader(csv_data.as_bytes())
+        .unwrap()
+        .map(|data| engine.process_transaction(&data.unwrap()))
+        .collect()
+}
+
+fn account_info(engine: &Engine) -> Vec<AccountInfo> {
+    let mut accounts = engine.accounts().map(AccountInfo::from).collect::<Vec<_>>();
+    accounts.sort_by_key(|account| account.client);
+    accounts
+}
+
+#[test]
+fn process_input() {
+    let mut engine = Engine::new();
+
+    let results = process(
+        &mut engine,
+        r"type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        deposit, 1, 3, 2.0
+        withdrawal, 1, 4, 1.5
+        withdrawal, 2, 5, 3.0
+        dispute, 2, 2
+        chargeback, 2, 2
+        deposit, 2, 6, 1.0",
+    );
+
+    assert!(results[..4].iter().all(Result::is_ok));
+    assert!(matches!(
+        results[4],
+        Err(EngineError::BalanceOperationError(..))
+    ));
+    assert!(results[5].is_ok() && results[6].is_ok());
+    assert!(matches!(
+        results[7],
+        Err(EngineError::InvalidTransactionData(
+            ValidationError::AccountLocked
+        ))
+    ));
+
+    assert_eq!(
+        account_info(&engine),
+        vec![
+            AccountInfo {
+                client: 1,
+                available: dec!(1.5),
+                held: dec!(0),
+                total: dec!(1.5),
+                locked: false,
+            },
+            AccountInfo {
+                client: 2,
+                available: dec!(0),
+                held: dec!(0),
+                total: dec!(0),
+                locked: true,
+            },
+        ]
+    );
+
+    let tx = engine.transaction(2).unwrap();
+
+    assert_eq!(tx.status, TransactionStatus::Cancelled);
+    assert_eq!(tx.history.len(), 2);
+}
+
+#[test]
+fn process_with_config() {
+    let config: EngineConfig = toml::from_str(
+        r#"
+        [[rules]]
+        type = "max_withdrawal"
+        amount = "5.0"
+
+        [fees]
+        house_account = 0
+
+        [fees.withdrawal]
+        type = "flat"
+        amount = "0.5"
+        "#,
+    )
+    .unwrap();
+
+    let mut engine = Engine::with_config(config);
+
+    let results = process(
+        &mut engine,
+        r"type, client, tx, amount
+        deposit, 1, 1, 10.0
+        withdrawal, 1, 2, 6.0
+        withdrawal, 1, 3, 5.0",
+    );
+
+    assert!(matches!(
+        results[1],
+        Err(EngineError::InvalidTransactionData(
+            ValidationError::RuleViolation {
+                rule: "max_withdrawal"
+            }
+        ))
+    ));
+
+    assert_eq!(engine.account(1).unwrap().balance().total(), dec!(4.5));
+    assert_eq!(engine.account(0).unwrap().balance().total(), dec!(0.5));
+}
+
+#[test]
+fn load_config_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/config.toml");
+
+    assert!(EngineConfig::from_file(path).is_ok());
+}
+
+#[test]
+#[cfg(not(any(feature = "narrow-ids", feature = "uuid-ids")))]
+fn wide_ids() {
+    let mut engine = Engine::new();
+
+    let results = process(
+        &mut engine,
+        r"type, client, tx, amount
+        deposit, 70000, 5000000000, 10.0
+        dispute, 70000, 5000000000",
+    );
+
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(engine.account(70000).unwrap().balance().held(), dec!(10.0));
+    assert!(engine.transaction(5_000_000_000).is_some());
+}
@@ -0,0 +1,160 @@
+//! Property-based tests of the balance invariants over random transaction sequences.
+
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use tx_toy::{
+    AccountInfo, ClientId, Engine, RawTransactionData, TransactionId, TransactionOutcome,
+    TransactionType,
+};
+
+/// Number of clients the sequences are spread across.
+const CLIENTS: u16 = 5;
+
+/// A generated step. Disputes and the like refer to the transaction created by the `tx`-th
+/// deposit or withdrawal, which may not exist. They're mostly sent by the client of the
+/// transaction, and by a random client otherwise.
+#[derive(Debug, Clone)]
+enum Step {
+    Deposit { client: u16, cents: u32 },
+    Withdrawal { client: u16, cents: u32 },
+    Dispute { client: Option<u16>, tx: usize },
+    Resolve { client: Option<u16>, tx: usize },
+    Chargeback { client: Option<u16>, tx: usize },
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let client = 1..=CLIENTS;
+    let cents = 1..100_000u32;
+    let tx = 1..40usize;
+    let sender = prop::option::weighted(0.2, client.clone());
+
+    prop_oneof![
+        3 => (client.clone(), cents.clone()).prop_map(|(client, cents)| Step::Deposit { client, cents }),
+        2 => (client.clone(), cents).prop_map(|(client, cents)| Step::Withdrawal { client, cents }),
+        2 => (sender.clone(), tx.clone()).prop_map(|(client, tx)| Step::Dispute { client, tx }),
+        1 => (sender.clone(), tx.clone()).prop_map(|(client, tx)| Step::Resolve { client, tx }),
+        1 => (sender, tx).prop_map(|(client, tx)| Step::Chargeback { client, tx }),
+    ]
+}
+
+/// Converts the steps to input rows, numbering the deposits and withdrawals in order.
+fn rows(steps: &[Step]) -> Vec<RawTransactionData> {
+    // Clients of the deposits and withdrawals, by their IDs minus one.
+    let mut owners = Vec::new();
+    let mut rows = Vec::new();
+
+    for step in steps {
+        let (ty, client, id, cents) = match *step {
+            Step::Deposit { client, cents } => {
+                owners.push(client);
+                (
+                    TransactionType::Deposit,
+                    Some(client),
+                    owners.len(),
+                    Some(cents),
+                )
+            }
+            Step::Withdrawal { client, cents } => {
+                owners.push(client);
+                (
+                    TransactionType::Withdrawal,
+                    Some(client),
+                    owners.len(),
+                    Some(cents),
+                )
+            }
+            Step::Dispute { client, tx } => (TransactionType::Dispute, client, tx, None),
+            Step::Resolve { client, tx } => (TransactionType::Resolve, client, tx, None),
+            Step::Chargeback { client, tx } => (TransactionType::Chargeback, client, tx, None),
+        };
+
+        // Unknown transactions are referred to by the first client.
+        let client = client.or_else(|| owners.get(id - 1).copied()).unwrap_or(1);
+
+        rows.push(RawTransactionData {
+            id: to_id::<TransactionId>(id as u16),
+            ty,
+            account_id: to_id::<ClientId>(client),
+            amount: cents.map(|cents| Decimal::new(cents.into(), 2)),
+            timestamp: None,
+        });
+    }
+
+    rows
+}
+
+/// Converts a generated number into an ID of any of the ID types.
+#[cfg(not(feature = "uuid-ids"))]
+fn to_id<T: From<u16>>(n: u16) -> T {
+    T::from(n)
+}
+
+/// Converts a generated number into an ID of any of the ID types.
+#[cfg(feature = "uuid-ids")]
+fn to_id<T: From<uuid::Uuid>>(n: u16) -> T {
+    uuid::Uuid::from_u64_pair(0, n.into()).into()
+}
+
+fn accounts(engine: &Engine) -> HashMap<ClientId, AccountInfo> {
+    engine
+        .accounts()
+        .map(|account| (account.id(), AccountInfo::from(account)))
+        .collect()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn balance_invariants(steps in prop::collection::vec(step(), 1..120)) {
+        let mut engine = Engine::new();
+
+        // Funds that entered and left the system, as accounted for by the applied rows.
+        let mut deposited = Decimal::ZERO;
+        let mut withdrawn = Decimal::ZERO;
+
+        for data in rows(&steps) {
+            let before = accounts(&engine);
+            let res = engine.process_transaction(&data);
+            let after = accounts(&engine);
+
+            if let Ok(TransactionOutcome::Applied) = res {
+                match data.ty {
+                    TransactionType::Deposit => deposited += data.amount.unwrap(),
+                    TransactionType::Withdrawal => withdrawn += data.amount.unwrap(),
+                    TransactionType::Chargeback => {
+                        withdrawn += engine.transaction(data.id).unwrap().amount
+                    }
+                    _ => {}
+                }
+            }
+
+            for (client, account) in &after {
+                prop_assert!(account.available >= Decimal::ZERO, "{data:?}: {account:?}");
+                prop_assert!(account.held >= Decimal::ZERO, "{data:?}: {account:?}");
+                prop_assert_eq!(account.total, account.available + account.held, "{:?}", data);
+
+                // Locked accounts are frozen.
+                if let Some(before) = before.get(client).filter(|account| account.locked) {
+                    prop_assert_eq!(account, before, "{:?}", data);
+                }
+            }
+
+            // Rejected rows change nothing, but may leave an empty account behind.
+            if res.is_err() {
+                for (client, account) in &after {
+                    match before.get(client) {
+                        Some(before) => prop_assert_eq!(account, before, "{:?}", data),
+                        None => prop_assert!(account.total.is_zero() && !account.locked, "{data:?}"),
+                    }
+                }
+            }
+
+            let total = after.values().map(|account| account.total).sum::<Decimal>();
+
+            prop_assert_eq!(total, deposited - withdrawn, "{:?}", data);
+            prop_assert_eq!(engine.audit(), Ok(()), "{:?}", data);
+        }
+    }
+}
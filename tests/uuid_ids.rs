@@ -0,0 +1,58 @@
+//! Processing of the example UUID input with the example UUID configuration.
+
+#![cfg(feature = "uuid-ids")]
+
+use rust_decimal_macros::dec;
+use tx_toy::{
+    AccountInfo, ClientId, Engine, EngineConfig, EngineError, InputStream, TransactionOutcome,
+    ValidationError,
+};
+
+fn client(id: &str) -> ClientId {
+    id.parse().unwrap()
+}
+
+#[test]
+fn example_input() -> anyhow::Result<()> {
+    let mut engine = Engine::with_config(EngineConfig::from_file("data/config.uuid.toml")?);
+
+    let results = InputStream::from_file("data/input.uuid.csv")?
+        .map(|data| engine.process_transaction(&data?.data))
+        .collect::<Vec<_>>();
+
+    assert!(results[..5]
+        .iter()
+        .all(|res| matches!(res, Ok(TransactionOutcome::Applied))));
+
+    // The blocklisted client.
+    assert!(matches!(
+        results[5],
+        Err(EngineError::InvalidTransactionData(
+            ValidationError::RuleViolation { .. }
+        ))
+    ));
+
+    let mut accounts = engine.accounts().map(AccountInfo::from).collect::<Vec<_>>();
+    accounts.sort_by_key(|account| account.client);
+
+    let expected = [
+        // The house account, credited with the withdrawal fee.
+        ("00000000-0000-0000-0000-000000000000", dec!(1.0), false),
+        // Withdrawn within the credit limit, along with the fee.
+        ("0b5d3c8e-6a4f-4e2d-9b1c-7f8e9d0a1b2c", dec!(-201.0), false),
+        // Charged back, the chargeback fee not covered by the available funds.
+        ("7c2a4e6f-8b1d-4c3e-a5f7-9b0c1d2e3f40", dec!(0), true),
+    ];
+
+    assert_eq!(accounts.len(), expected.len());
+
+    for (account, (id, total, locked)) in accounts.iter().zip(expected) {
+        assert_eq!(account.client, client(id));
+        assert_eq!(account.total, total);
+        assert_eq!(account.locked, locked);
+    }
+
+    assert!(engine.audit().is_ok());
+
+    Ok(())
+}